@@ -1,10 +1,32 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::f32::consts::PI;
+use std::fmt;
+use std::mem;
+
 use gfx;
 use gfx::handle::{DepthStencilView, RenderTargetView};
 use gfx::traits::*;
 use gfx_text;
 use vecmath::*;
 
+use fill_renderer::FillRenderer;
 use line_renderer::LineRenderer;
+use text_layout::{
+    advance,
+    caret_offset,
+    clip_piece,
+    glyph_count,
+    layout_spans,
+    parse_text,
+    replace_missing,
+    ScreenAnchor,
+    ScreenOrigin,
+    Span,
+    TextOptions,
+};
+use utils::{bounding_sphere, clamp_alpha, frustum_corners, icosphere, plane_basis, project_to_screen, scale_alpha, to_pixel, transform_point};
 
 #[derive(Debug)]
 pub enum DebugRendererError {
@@ -13,6 +35,30 @@ pub enum DebugRendererError {
     GfxTextError(gfx_text::Error)
 }
 
+impl fmt::Display for DebugRendererError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DebugRendererError::PipelineStateError(ref err) =>
+                write!(f, "Could not create pipeline state: {}", err),
+            DebugRendererError::UpdateError(ref err) =>
+                write!(f, "Could not update buffer: {}", err),
+            // gfx_text errors (including font loading failures) only implement Debug.
+            DebugRendererError::GfxTextError(ref err) =>
+                write!(f, "Text renderer error: {:?}", err),
+        }
+    }
+}
+
+impl Error for DebugRendererError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            DebugRendererError::PipelineStateError(ref err) => Some(err),
+            DebugRendererError::UpdateError(ref err) => Some(err),
+            DebugRendererError::GfxTextError(_) => None,
+        }
+    }
+}
+
 impl From<gfx::PipelineStateError<String>> for DebugRendererError {
     fn from(err: gfx::PipelineStateError<String>) -> DebugRendererError {
         DebugRendererError::PipelineStateError(err)
@@ -31,10 +77,48 @@ impl From<gfx_text::Error> for DebugRendererError {
     }
 }
 
+///
+/// Where queued text goes once `render` knows the screen size and projection
+///
+enum TextPlacement {
+    Screen([i32; 2]),
+    Anchored(ScreenAnchor, [i32; 2]),
+    World([f32; 3]),
+}
+
+type QueuedText = (TextPlacement, Vec<Span>, TextOptions);
+
+///
+/// Move world text ahead of screen and anchored text, keeping the order
+/// within each, so that gfx_text draws screen text over it
+///
+fn world_text_first(queued_text: &mut [QueuedText]) {
+    queued_text.sort_by_key(|entry| match entry.0 {
+        TextPlacement::World(_) => 0,
+        TextPlacement::Screen(_) | TextPlacement::Anchored(_, _) => 1,
+    });
+}
+
 pub struct DebugRenderer<R: gfx::Resources, F: Factory<R>> {
     line_renderer: LineRenderer<R>,
+    fill_renderer: FillRenderer<R>,
     text_renderer: gfx_text::Renderer<R, F>,
     factory: F,
+    min_alpha: f32,
+    global_alpha: f32,
+    circle_segments: usize,
+    side_lines: usize,
+    ray_length: f32,
+    ray_origin_marker: Option<f32>,
+    missing_glyph_fallback: Option<char>,
+    missing_glyph_tint: Option<[f32; 4]>,
+    glyph_known: RefCell<HashMap<char, bool>>,
+    queued_text: Vec<QueuedText>,
+    screen_origin: ScreenOrigin,
+    world_text_near_clip: f32,
+    screen_text_on_top: bool,
+    glyph_count: usize,
+    pixel_snap: bool,
 }
 
 impl<R: gfx::Resources, F: Factory<R>> DebugRenderer<R, F> {
@@ -47,19 +131,207 @@ impl<R: gfx::Resources, F: Factory<R>> DebugRenderer<R, F> {
 
         let mut factory = factory;
         let line_renderer = LineRenderer::new(&mut factory, initial_buffer_size);
+        let fill_renderer = FillRenderer::new(&mut factory, initial_buffer_size);
 
         Ok(DebugRenderer {
-            line_renderer: line_renderer,
-            text_renderer: text_renderer,
-            factory: factory,
+            line_renderer,
+            fill_renderer,
+            text_renderer,
+            factory,
+            min_alpha: 0.0,
+            global_alpha: 1.0,
+            circle_segments: 32,
+            side_lines: 4,
+            ray_length: 1000.0,
+            ray_origin_marker: None,
+            missing_glyph_fallback: None,
+            missing_glyph_tint: None,
+            glyph_known: RefCell::new(HashMap::new()),
+            queued_text: Vec::new(),
+            screen_origin: ScreenOrigin::TopLeft,
+            world_text_near_clip: 0.0,
+            screen_text_on_top: false,
+            glyph_count: 0,
+            pixel_snap: true,
         })
     }
 
+    ///
+    /// Clamp the alpha of every color passed to the draw methods to at least
+    /// `min_alpha`, so nothing is drawn fully invisible. Defaults to 0.0,
+    /// which leaves colors unchanged.
+    ///
+    pub fn set_min_alpha(&mut self, min_alpha: f32) {
+        self.min_alpha = min_alpha;
+    }
+
+    ///
+    /// Multiply the alpha of everything drawn by `render` by `alpha`, to fade
+    /// the whole overlay in or out. This applies on top of each color's own
+    /// alpha and `set_min_alpha`. Defaults to 1.0.
+    ///
+    pub fn set_global_alpha(&mut self, alpha: f32) {
+        self.global_alpha = alpha;
+    }
+
+    ///
+    /// Replace the text renderer, returning the old one. This lets text be
+    /// drawn with a small placeholder font while a larger one loads, e.g. on
+    /// another thread, and then switch over. Text already queued is drawn
+    /// with the new renderer.
+    ///
+    pub fn set_text_renderer(&mut self, text_renderer: gfx_text::Renderer<R, F>) -> gfx_text::Renderer<R, F> {
+        // Which glyphs are missing depends on the font
+        self.glyph_known.borrow_mut().clear();
+        mem::replace(&mut self.text_renderer, text_renderer)
+    }
+
+    ///
+    /// Set the number of line segments used to approximate a full circle in
+    /// curved primitives such as spheres. Defaults to 32.
+    ///
+    pub fn set_circle_segments(&mut self, segments: usize) {
+        self.circle_segments = segments.max(3);
+    }
+
+    ///
+    /// Set the number of lines drawn along the sides of cylinders, cones and
+    /// capsules. Defaults to 4.
+    ///
+    pub fn set_side_lines(&mut self, side_lines: usize) {
+        self.side_lines = side_lines;
+    }
+
+    ///
+    /// Set how far rays from `draw_ray` extend. Defaults to 1000.0, a typical
+    /// far clip distance.
+    ///
+    pub fn set_ray_length(&mut self, length: f32) {
+        self.ray_length = length;
+    }
+
+    ///
+    /// Draw a marker of the given size at the origin of every ray, or none
+    /// (the default)
+    ///
+    pub fn set_ray_origin_marker(&mut self, size: Option<f32>) {
+        self.ray_origin_marker = size;
+    }
+
+    ///
+    /// Draw `fallback` in place of characters the text renderer's font has no
+    /// glyph for, instead of silently leaving them out (the default)
+    ///
+    pub fn set_missing_glyph_fallback(&mut self, fallback: Option<char>) {
+        self.missing_glyph_fallback = fallback;
+    }
+
+    ///
+    /// Set the corner of the screen that pixel positions passed to the
+    /// screen-space draw methods, and `TextOptions::clip`, are measured from.
+    /// Defaults to `ScreenOrigin::TopLeft`, with y growing downwards.
+    ///
+    pub fn set_screen_origin(&mut self, origin: ScreenOrigin) {
+        self.screen_origin = origin;
+    }
+
+    ///
+    /// Leave out world text whose clip-space depth is below `near_clip`, so
+    /// labels right in front of the camera don't cover the screen. Defaults to
+    /// 0.0, which only leaves out text behind the viewer.
+    ///
+    pub fn set_world_text_near_clip(&mut self, near_clip: f32) {
+        self.world_text_near_clip = near_clip;
+    }
+
+    ///
+    /// Round the projected position of world text to the nearest pixel (the
+    /// default), or with `false` drop its fraction instead. gfx_text only
+    /// draws at whole pixels, so text is never placed between them.
+    ///
+    pub fn set_pixel_snap(&mut self, snap: bool) {
+        self.pixel_snap = snap;
+    }
+
+    ///
+    /// Draw all world text before any screen and anchored text, so labels in
+    /// the scene never cover the screen overlay. Defaults to `false`, which
+    /// draws text in the order it was queued.
+    ///
+    pub fn set_screen_text_on_top(&mut self, on_top: bool) {
+        self.screen_text_on_top = on_top;
+    }
+
+    ///
+    /// Draw characters replaced by the missing-glyph fallback in `tint`
+    /// instead of the color of the text around them, so they stand out.
+    /// Defaults to `None`, which keeps the text's color.
+    ///
+    pub fn set_missing_glyph_tint(&mut self, tint: Option<[f32; 4]>) {
+        self.missing_glyph_tint = tint;
+    }
+
+    ///
+    /// Get the number of glyphs the last `render` handed to the text renderer,
+    /// counting every copy drawn for shadows and outlines, e.g. to log whether
+    /// any text was drawn that frame
+    ///
+    pub fn last_glyph_count(&self) -> usize {
+        self.glyph_count
+    }
+
+    fn is_glyph_missing(&self, ch: char) -> bool {
+        if ch == '\n' || ch == '\t' {
+            return false;
+        }
+        let text_renderer = &self.text_renderer;
+        !*self.glyph_known.borrow_mut().entry(ch).or_insert_with(|| {
+            // gfx_text skips characters missing from its font, so they neither
+            // advance nor have any extent. Glyphs such as combining marks exist
+            // but don't advance, so both have to be checked.
+            let width_of = |text: &str| text_renderer.measure(text).0;
+            let ch = ch.to_string();
+            advance(&ch, width_of) != 0 || width_of(&ch) != 0
+        })
+    }
+
+    fn replace_missing_glyphs(&self, text: &str) -> String {
+        match self.missing_glyph_fallback {
+            Some(fallback) => text.chars().map(|ch| if self.is_glyph_missing(ch) { fallback } else { ch }).collect(),
+            None => text.to_string(),
+        }
+    }
+
+    fn replace_missing_glyphs_in_span(&self, span: &Span) -> Vec<Span> {
+        match self.missing_glyph_fallback {
+            Some(fallback) => replace_missing(span, fallback, self.missing_glyph_tint, |ch| self.is_glyph_missing(ch)),
+            None => vec![span.clone()],
+        }
+    }
+
+    fn adjust_color(&self, color: [f32; 4]) -> [f32; 4] {
+        clamp_alpha(color, self.min_alpha)
+    }
+
+    ///
+    /// The color text is handed to gfx_text in. Unlike lines and fills, which
+    /// are faded as they're uploaded, text is faded here.
+    ///
+    fn text_color(&self, color: [f32; 4]) -> [f32; 4] {
+        scale_alpha(self.adjust_color(color), self.global_alpha)
+    }
+
     pub fn draw_line(&mut self, start: [f32; 3], end: [f32; 3], color: [f32; 4]) {
+        let color = self.adjust_color(color);
         self.line_renderer.draw_line(start, end, color);
     }
 
+    ///
+    /// Draw a 3-axis cross centered on `position`, extending `size` units in
+    /// each direction along X, Y and Z
+    ///
     pub fn draw_marker(&mut self, position: [f32; 3], size: f32, color: [f32; 4]) {
+        let color = self.adjust_color(color);
         self.line_renderer.draw_line(vec3_add(position, [size, 0.0, 0.0]),
                                      vec3_add(position, [-size, 0.0, 0.0]),
                                      color);
@@ -73,22 +345,1049 @@ impl<R: gfx::Resources, F: Factory<R>> DebugRenderer<R, F> {
                                      color);
     }
 
+    ///
+    /// Draw a wireframe sphere as three orthogonal great circles
+    ///
+    pub fn draw_sphere(&mut self, center: [f32; 3], radius: f32, color: [f32; 4]) {
+        let x = [radius, 0.0, 0.0];
+        let y = [0.0, radius, 0.0];
+        let z = [0.0, 0.0, radius];
+        self.draw_elliptic_arc(center, x, y, 0.0, 2.0 * PI, color);
+        self.draw_elliptic_arc(center, y, z, 0.0, 2.0 * PI, color);
+        self.draw_elliptic_arc(center, z, x, 0.0, 2.0 * PI, color);
+    }
+
+    ///
+    /// Draw the 12 edges of an axis-aligned box
+    ///
+    pub fn draw_aabb(&mut self, min: [f32; 3], max: [f32; 3], color: [f32; 4]) {
+        let mut corners = [[0.0; 3]; 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            *corner = [
+                if i & 1 == 0 { min[0] } else { max[0] },
+                if i & 2 == 0 { min[1] } else { max[1] },
+                if i & 4 == 0 { min[2] } else { max[2] },
+            ];
+        }
+        self.draw_box_edges(&corners, color);
+    }
+
+    ///
+    /// Draw the edges of a box with the given half extents, centered on the
+    /// origin of the column-major `transform`
+    ///
+    pub fn draw_obb(&mut self, transform: [[f32; 4]; 4], half_extents: [f32; 3], color: [f32; 4]) {
+        let mut corners = [[0.0; 3]; 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            let local = [
+                if i & 1 == 0 { -half_extents[0] } else { half_extents[0] },
+                if i & 2 == 0 { -half_extents[1] } else { half_extents[1] },
+                if i & 4 == 0 { -half_extents[2] } else { half_extents[2] },
+            ];
+            *corner = transform_point(transform, local);
+        }
+        self.draw_box_edges(&corners, color);
+    }
+
+    ///
+    /// Draw a circle in the plane through `center` perpendicular to `normal`
+    ///
+    pub fn draw_circle(&mut self, center: [f32; 3], normal: [f32; 3], radius: f32, color: [f32; 4]) {
+        let (u, v) = plane_basis(normal);
+        self.draw_elliptic_arc(center, vec3_scale(u, radius), vec3_scale(v, radius), 0.0, 2.0 * PI, color);
+    }
+
+    ///
+    /// Draw an arc of a circle in the plane perpendicular to `normal`. Angles
+    /// are in radians, measured around `normal` from an arbitrary axis in the
+    /// plane (the same one `draw_circle` starts from).
+    ///
+    pub fn draw_arc(
+        &mut self,
+        center: [f32; 3],
+        normal: [f32; 3],
+        radius: f32,
+        start_angle: f32,
+        end_angle: f32,
+        color: [f32; 4],
+    ) {
+        let (u, v) = plane_basis(normal);
+        self.draw_elliptic_arc(center, vec3_scale(u, radius), vec3_scale(v, radius), start_angle, end_angle, color);
+    }
+
+    ///
+    /// Draw an ellipse around `center` spanned by two (possibly non-unit) axis
+    /// vectors, which should be perpendicular
+    ///
+    pub fn draw_ellipse(&mut self, center: [f32; 3], axis_a: [f32; 3], axis_b: [f32; 3], color: [f32; 4]) {
+        self.draw_elliptic_arc(center, axis_a, axis_b, 0.0, 2.0 * PI, color);
+    }
+
+    ///
+    /// Draw a line from `from` to `to` with a four-sided head of length
+    /// `head_size` at `to`
+    ///
+    pub fn draw_arrow(&mut self, from: [f32; 3], to: [f32; 3], head_size: f32, color: [f32; 4]) {
+        self.draw_line(from, to, color);
+
+        let shaft = vec3_sub(to, from);
+        if vec3_len(shaft) == 0.0 {
+            return;
+        }
+
+        let direction = vec3_normalized(shaft);
+        let (u, v) = plane_basis(direction);
+        let base = vec3_sub(to, vec3_scale(direction, head_size));
+        let spread = head_size * 0.5;
+        for &side in &[u, v, vec3_neg(u), vec3_neg(v)] {
+            self.draw_line(to, vec3_add(base, vec3_scale(side, spread)), color);
+        }
+    }
+
+    ///
+    /// Draw the X, Y and Z basis vectors of a column-major `transform` in red,
+    /// green and blue, each `scale` units long
+    ///
+    pub fn draw_axes(&mut self, transform: [[f32; 4]; 4], scale: f32) {
+        let origin = transform_point(transform, [0.0, 0.0, 0.0]);
+        let axes = [
+            ([scale, 0.0, 0.0], [1.0, 0.0, 0.0, 1.0]),
+            ([0.0, scale, 0.0], [0.0, 1.0, 0.0, 1.0]),
+            ([0.0, 0.0, scale], [0.0, 0.0, 1.0, 1.0]),
+        ];
+        for &(axis, color) in &axes {
+            self.draw_line(origin, transform_point(transform, axis), color);
+        }
+    }
+
+    ///
+    /// Draw a square grid of `cell_count` by `cell_count` cells centered on
+    /// `origin`, in the plane perpendicular to `normal`. If `major_lines` is
+    /// given as `(every, color)`, every `every`th line (counting from the edge)
+    /// is drawn in that color instead.
+    ///
+    pub fn draw_grid(
+        &mut self,
+        origin: [f32; 3],
+        normal: [f32; 3],
+        cell_size: f32,
+        cell_count: usize,
+        color: [f32; 4],
+        major_lines: Option<(usize, [f32; 4])>,
+    ) {
+        let (u, v) = plane_basis(normal);
+        let half = cell_size * (cell_count as f32) * 0.5;
+        let corner = vec3_sub(origin, vec3_add(vec3_scale(u, half), vec3_scale(v, half)));
+
+        for i in 0..(cell_count + 1) {
+            let line_color = match major_lines {
+                Some((every, major_color)) if every > 0 && i % every == 0 => major_color,
+                _ => color,
+            };
+            let offset = cell_size * (i as f32);
+            let along_u = vec3_add(corner, vec3_scale(u, offset));
+            let along_v = vec3_add(corner, vec3_scale(v, offset));
+            self.draw_line(along_u, vec3_add(along_u, vec3_scale(v, 2.0 * half)), line_color);
+            self.draw_line(along_v, vec3_add(along_v, vec3_scale(u, 2.0 * half)), line_color);
+        }
+    }
+
+    ///
+    /// Draw the edges of the view frustum described by `inverse_view_proj`, by
+    /// unprojecting the corners of the OpenGL clip-space cube
+    ///
+    pub fn draw_frustum(&mut self, inverse_view_proj: [[f32; 4]; 4], color: [f32; 4]) {
+        let corners = frustum_corners(inverse_view_proj, -1.0, 1.0);
+        self.draw_box_edges(&corners, color);
+    }
+
+    ///
+    /// Draw a capsule wireframe: a cylinder of `radius` between `p0` and `p1`
+    /// capped with hemispheres
+    ///
+    pub fn draw_capsule(&mut self, p0: [f32; 3], p1: [f32; 3], radius: f32, color: [f32; 4]) {
+        let axis = vec3_sub(p1, p0);
+        if vec3_len(axis) == 0.0 {
+            self.draw_sphere(p0, radius, color);
+            return;
+        }
+
+        let direction = vec3_normalized(axis);
+        let (u, v) = plane_basis(direction);
+        self.draw_dome(p1, direction, u, v, radius, color);
+        self.draw_dome(p0, vec3_neg(direction), u, v, radius, color);
+
+        for side in self.side_directions(u, v) {
+            let offset = vec3_scale(side, radius);
+            self.draw_line(vec3_add(p0, offset), vec3_add(p1, offset), color);
+        }
+    }
+
+    ///
+    /// Draw a cone with its tip at `apex`, opening along `direction` for
+    /// `length` units with the half-angle `angle` (in radians)
+    ///
+    pub fn draw_cone(&mut self, apex: [f32; 3], direction: [f32; 3], length: f32, angle: f32, color: [f32; 4]) {
+        let direction = vec3_normalized(direction);
+        let (u, v) = plane_basis(direction);
+        let base = vec3_add(apex, vec3_scale(direction, length));
+        let radius = length * angle.tan();
+
+        self.draw_elliptic_arc(base, vec3_scale(u, radius), vec3_scale(v, radius), 0.0, 2.0 * PI, color);
+        for side in self.side_directions(u, v) {
+            self.draw_line(apex, vec3_add(base, vec3_scale(side, radius)), color);
+        }
+    }
+
+    ///
+    /// Draw a cylinder of `radius` between the centers of its end caps `p0`
+    /// and `p1`, as two circles joined by side lines
+    ///
+    pub fn draw_cylinder(&mut self, p0: [f32; 3], p1: [f32; 3], radius: f32, color: [f32; 4]) {
+        let axis = vec3_sub(p1, p0);
+        if vec3_len(axis) == 0.0 {
+            self.draw_circle(p0, [0.0, 1.0, 0.0], radius, color);
+            return;
+        }
+
+        let (u, v) = plane_basis(axis);
+        let u = vec3_scale(u, radius);
+        let v = vec3_scale(v, radius);
+        self.draw_elliptic_arc(p0, u, v, 0.0, 2.0 * PI, color);
+        self.draw_elliptic_arc(p1, u, v, 0.0, 2.0 * PI, color);
+
+        for side in self.side_directions(u, v) {
+            self.draw_line(vec3_add(p0, side), vec3_add(p1, side), color);
+        }
+    }
+
+    ///
+    /// Draw a torus around `center` whose ring lies in the plane perpendicular
+    /// to `normal`: four rings along the tube plus one cross-section of the
+    /// tube per side line
+    ///
+    pub fn draw_torus(
+        &mut self,
+        center: [f32; 3],
+        normal: [f32; 3],
+        major_radius: f32,
+        minor_radius: f32,
+        color: [f32; 4],
+    ) {
+        let normal = vec3_normalized(normal);
+        let (u, v) = plane_basis(normal);
+
+        for &radius in &[major_radius - minor_radius, major_radius + minor_radius] {
+            self.draw_elliptic_arc(center, vec3_scale(u, radius), vec3_scale(v, radius), 0.0, 2.0 * PI, color);
+        }
+        for &height in &[-minor_radius, minor_radius] {
+            let ring_center = vec3_add(center, vec3_scale(normal, height));
+            self.draw_elliptic_arc(ring_center, vec3_scale(u, major_radius), vec3_scale(v, major_radius), 0.0, 2.0 * PI, color);
+        }
+
+        for side in self.side_directions(u, v) {
+            let tube_center = vec3_add(center, vec3_scale(side, major_radius));
+            self.draw_elliptic_arc(tube_center, vec3_scale(side, minor_radius), vec3_scale(normal, minor_radius), 0.0, 2.0 * PI, color);
+        }
+    }
+
+    ///
+    /// Draw a ray from `origin` along `direction`, out to the length set with
+    /// `set_ray_length`
+    ///
+    pub fn draw_ray(&mut self, origin: [f32; 3], direction: [f32; 3], color: [f32; 4]) {
+        if let Some(size) = self.ray_origin_marker {
+            self.draw_marker(origin, size, color);
+        }
+        if vec3_len(direction) == 0.0 {
+            return;
+        }
+        let end = vec3_add(origin, vec3_scale(vec3_normalized(direction), self.ray_length));
+        self.draw_line(origin, end, color);
+    }
+
+    ///
+    /// Draw a square of side `size` centered on `point` in the plane
+    /// perpendicular to `normal`, plus an arrow along the normal
+    ///
+    pub fn draw_plane(&mut self, point: [f32; 3], normal: [f32; 3], size: f32, color: [f32; 4]) {
+        let normal = vec3_normalized(normal);
+        let (u, v) = plane_basis(normal);
+        let u = vec3_scale(u, size * 0.5);
+        let v = vec3_scale(v, size * 0.5);
+        let corners = [
+            vec3_add(point, vec3_add(u, v)),
+            vec3_add(point, vec3_sub(v, u)),
+            vec3_sub(point, vec3_add(u, v)),
+            vec3_add(point, vec3_sub(u, v)),
+        ];
+        for i in 0..4 {
+            self.draw_line(corners[i], corners[(i + 1) % 4], color);
+        }
+
+        let tip = vec3_add(point, vec3_scale(normal, size * 0.5));
+        self.draw_arrow(point, tip, size * 0.1, color);
+    }
+
+    ///
+    /// Draw lines connecting each point to the next
+    ///
+    pub fn draw_polyline(&mut self, points: &[[f32; 3]], color: [f32; 4]) {
+        for pair in points.windows(2) {
+            self.draw_line(pair[0], pair[1], color);
+        }
+    }
+
+    ///
+    /// Draw lines connecting each point to the next, and the last point back
+    /// to the first
+    ///
+    pub fn draw_line_loop(&mut self, points: &[[f32; 3]], color: [f32; 4]) {
+        self.draw_polyline(points, color);
+        if points.len() > 2 {
+            self.draw_line(points[points.len() - 1], points[0], color);
+        }
+    }
+
+    ///
+    /// Draw a cubic Bezier curve from `p0` to `p1` with control points `c0` and
+    /// `c1`, using `segments` line segments
+    ///
+    pub fn draw_bezier(
+        &mut self,
+        p0: [f32; 3],
+        c0: [f32; 3],
+        c1: [f32; 3],
+        p1: [f32; 3],
+        segments: usize,
+        color: [f32; 4],
+    ) {
+        let segments = segments.max(1);
+        let point = |t: f32| {
+            let s = 1.0 - t;
+            vec3_add(
+                vec3_add(vec3_scale(p0, s * s * s), vec3_scale(c0, 3.0 * s * s * t)),
+                vec3_add(vec3_scale(c1, 3.0 * s * t * t), vec3_scale(p1, t * t * t)),
+            )
+        };
+
+        let mut previous = p0;
+        for i in 1..(segments + 1) {
+            let next = point((i as f32) / (segments as f32));
+            self.draw_line(previous, next, color);
+            previous = next;
+        }
+    }
+
+    ///
+    /// Draw a uniform Catmull-Rom spline passing through every control point,
+    /// using `segments_per_span` line segments between consecutive points. The
+    /// end points are repeated to give the first and last spans a tangent.
+    ///
+    pub fn draw_spline(&mut self, points: &[[f32; 3]], segments_per_span: usize, color: [f32; 4]) {
+        if points.len() < 2 {
+            return;
+        }
+        let segments = segments_per_span.max(1);
+        let last = points.len() - 1;
+
+        for i in 0..last {
+            let p0 = points[if i == 0 { 0 } else { i - 1 }];
+            let p1 = points[i];
+            let p2 = points[i + 1];
+            let p3 = points[(i + 2).min(last)];
+
+            let mut previous = p1;
+            for j in 1..(segments + 1) {
+                let t = (j as f32) / (segments as f32);
+                let (t2, t3) = (t * t, t * t * t);
+                let next = vec3_scale(vec3_add(
+                    vec3_add(vec3_scale(p1, 2.0), vec3_scale(vec3_sub(p2, p0), t)),
+                    vec3_add(
+                        vec3_scale([
+                            2.0 * p0[0] - 5.0 * p1[0] + 4.0 * p2[0] - p3[0],
+                            2.0 * p0[1] - 5.0 * p1[1] + 4.0 * p2[1] - p3[1],
+                            2.0 * p0[2] - 5.0 * p1[2] + 4.0 * p2[2] - p3[2],
+                        ], t2),
+                        vec3_scale([
+                            3.0 * (p1[0] - p2[0]) + p3[0] - p0[0],
+                            3.0 * (p1[1] - p2[1]) + p3[1] - p0[1],
+                            3.0 * (p1[2] - p2[2]) + p3[2] - p0[2],
+                        ], t3),
+                    ),
+                ), 0.5);
+                self.draw_line(previous, next, color);
+                previous = next;
+            }
+        }
+    }
+
+    ///
+    /// Draw the outline of the triangle `a`, `b`, `c`
+    ///
+    pub fn draw_triangle(&mut self, a: [f32; 3], b: [f32; 3], c: [f32; 3], color: [f32; 4]) {
+        self.draw_line_loop(&[a, b, c], color);
+    }
+
+    ///
+    /// Draw the outline of a polygon, closing the loop from the last point
+    /// back to the first
+    ///
+    pub fn draw_polygon(&mut self, points: &[[f32; 3]], color: [f32; 4]) {
+        self.draw_line_loop(points, color);
+    }
+
+    ///
+    /// Draw a hemisphere over `center` bulging towards `up`, as its base circle
+    /// plus two half circles through the pole
+    ///
+    pub fn draw_hemisphere(&mut self, center: [f32; 3], up: [f32; 3], radius: f32, color: [f32; 4]) {
+        let up = vec3_normalized(up);
+        let (u, v) = plane_basis(up);
+        self.draw_dome(center, up, u, v, radius, color);
+    }
+
+    ///
+    /// Draw a box with the given half extents and rounded edges of
+    /// `corner_radius`, centered on the origin of the column-major `transform`.
+    /// Each face is outlined by its flat part, and the faces are joined by
+    /// quarter circles at the ends of every edge.
+    ///
+    pub fn draw_rounded_box(
+        &mut self,
+        transform: [[f32; 4]; 4],
+        half_extents: [f32; 3],
+        corner_radius: f32,
+        color: [f32; 4],
+    ) {
+        let r = corner_radius.max(0.0).min(half_extents[0]).min(half_extents[1]).min(half_extents[2]);
+        let inner = [half_extents[0] - r, half_extents[1] - r, half_extents[2] - r];
+        let axis = |k: usize, length: f32| {
+            let mut a = [0.0; 3];
+            a[k] = length;
+            a
+        };
+
+        for k in 0..3 {
+            let (i, j) = ((k + 1) % 3, (k + 2) % 3);
+            for &si in &[-1.0, 1.0] {
+                for &sj in &[-1.0, 1.0] {
+                    let edge_center = vec3_add(axis(i, si * inner[i]), axis(j, sj * inner[j]));
+                    let start = vec3_sub(edge_center, axis(k, inner[k]));
+                    let end = vec3_add(edge_center, axis(k, inner[k]));
+                    let normal_i = axis(i, si * r);
+                    let normal_j = axis(j, sj * r);
+
+                    // Flat strips on the two faces meeting at this edge
+                    for &offset in &[normal_i, normal_j] {
+                        self.draw_line(
+                            transform_point(transform, vec3_add(start, offset)),
+                            transform_point(transform, vec3_add(end, offset)),
+                            color,
+                        );
+                    }
+
+                    // Quarter circles joining them at both ends
+                    for &local_center in &[start, end] {
+                        let center = transform_point(transform, local_center);
+                        let a = vec3_sub(transform_point(transform, vec3_add(local_center, normal_i)), center);
+                        let b = vec3_sub(transform_point(transform, vec3_add(local_center, normal_j)), center);
+                        self.draw_elliptic_arc(center, a, b, 0.0, 0.5 * PI, color);
+                    }
+                }
+            }
+        }
+    }
+
+    ///
+    /// Draw a line between two pixel positions measured from the corner set
+    /// with `set_screen_origin`, in front of everything in the scene. Screen
+    /// lines are drawn over screen fills and under screen text, whatever order
+    /// they were queued in.
+    ///
+    pub fn draw_line_on_screen(&mut self, start: [i32; 2], end: [i32; 2], color: [f32; 4]) {
+        let color = self.adjust_color(color);
+        // Offset to pixel centers so one pixel wide lines don't straddle two rows
+        self.line_renderer.draw_line_on_screen(
+            [start[0] as f32 + 0.5, start[1] as f32 + 0.5],
+            [end[0] as f32 + 0.5, end[1] as f32 + 0.5],
+            color,
+            self.screen_origin,
+        );
+    }
+
+    ///
+    /// Draw the outline of the screen rectangle `[x, y, width, height]`, in
+    /// pixels measured from the corner set with `set_screen_origin`, so that
+    /// `y` is its top edge from the top left and its bottom edge from the
+    /// bottom left
+    ///
+    pub fn draw_rect_on_screen(&mut self, rect: [i32; 4], color: [f32; 4]) {
+        let [x, y, w, h] = rect;
+        let corners = [[x, y], [x + w, y], [x + w, y + h], [x, y + h]];
+        for i in 0..4 {
+            self.draw_line_on_screen(corners[i], corners[(i + 1) % 4], color);
+        }
+    }
+
+    ///
+    /// Draw a circle in the plane perpendicular to `normal` with `spokes`
+    /// evenly spaced radial lines from the center
+    ///
+    pub fn draw_disc(&mut self, center: [f32; 3], normal: [f32; 3], radius: f32, spokes: usize, color: [f32; 4]) {
+        let (u, v) = plane_basis(normal);
+        let u = vec3_scale(u, radius);
+        let v = vec3_scale(v, radius);
+        self.draw_elliptic_arc(center, u, v, 0.0, 2.0 * PI, color);
+
+        for i in 0..spokes {
+            let t = 2.0 * PI * (i as f32) / (spokes as f32);
+            let rim = vec3_add(center, vec3_add(vec3_scale(u, t.cos()), vec3_scale(v, t.sin())));
+            self.draw_line(center, rim, color);
+        }
+    }
+
+    ///
+    /// Draw an approximate bounding sphere (found with Ritter's algorithm)
+    /// around a set of points. Nothing is drawn for an empty set.
+    ///
+    pub fn draw_bounding_sphere_for_points(&mut self, points: &[[f32; 3]], color: [f32; 4]) {
+        if let Some((center, radius)) = bounding_sphere(points) {
+            self.draw_sphere(center, radius, color);
+        }
+    }
+
+    ///
+    /// Draw the sub-frustums of a split view frustum, as used for cascaded
+    /// shadow maps. `near` and `far` are the view-space depths of the camera's
+    /// clip planes and `splits` the depths in between, in increasing order.
+    /// Cascade `i` is drawn in `colors[i % colors.len()]`.
+    ///
+    pub fn draw_frustum_splits(
+        &mut self,
+        inverse_view_proj: [[f32; 4]; 4],
+        near: f32,
+        far: f32,
+        splits: &[f32],
+        colors: &[[f32; 4]],
+    ) {
+        if colors.is_empty() || far <= near {
+            return;
+        }
+
+        let corners = frustum_corners(inverse_view_proj, -1.0, 1.0);
+        // View depth is linear along each edge from the near to the far plane
+        let corners_at = |depth: f32| {
+            let t = (depth - near) / (far - near);
+            let mut plane = [[0.0; 3]; 4];
+            for (i, corner) in plane.iter_mut().enumerate() {
+                *corner = vec3_add(corners[i], vec3_scale(vec3_sub(corners[i + 4], corners[i]), t));
+            }
+            plane
+        };
+
+        let mut bounds = vec![near];
+        bounds.extend(splits.iter().cloned().filter(|&d| d > near && d < far));
+        bounds.push(far);
+
+        for (i, pair) in bounds.windows(2).enumerate() {
+            let (front, back) = (corners_at(pair[0]), corners_at(pair[1]));
+            let mut cascade = [[0.0; 3]; 8];
+            cascade[..4].copy_from_slice(&front);
+            cascade[4..].copy_from_slice(&back);
+            self.draw_box_edges(&cascade, colors[i % colors.len()]);
+        }
+    }
+
+    ///
+    /// Draw a solid triangle, blended with what's behind it by the color's
+    /// alpha
+    ///
+    pub fn draw_triangle_filled(&mut self, a: [f32; 3], b: [f32; 3], c: [f32; 3], color: [f32; 4]) {
+        let color = self.adjust_color(color);
+        self.fill_renderer.draw_triangle(a, b, c, color);
+    }
+
+    ///
+    /// Draw the six faces of an axis-aligned box, blended by the color's alpha,
+    /// and optionally its edges in `edge_color`
+    ///
+    pub fn draw_aabb_filled(&mut self, min: [f32; 3], max: [f32; 3], color: [f32; 4], edge_color: Option<[f32; 4]>) {
+        let mut corners = [[0.0; 3]; 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            *corner = [
+                if i & 1 == 0 { min[0] } else { max[0] },
+                if i & 2 == 0 { min[1] } else { max[1] },
+                if i & 4 == 0 { min[2] } else { max[2] },
+            ];
+        }
+        self.fill_box_faces(&corners, color);
+        if let Some(edge_color) = edge_color {
+            self.draw_box_edges(&corners, edge_color);
+        }
+    }
+
+    ///
+    /// Draw a solid low-poly sphere, blended by the color's alpha. Faces are
+    /// shaded by a fixed light from above so the sphere reads as a volume.
+    ///
+    pub fn draw_sphere_filled(&mut self, center: [f32; 3], radius: f32, color: [f32; 4]) {
+        let color = self.adjust_color(color);
+        let light = vec3_normalized([0.3, 1.0, 0.5]);
+        let (vertices, triangles) = icosphere(1);
+
+        let indices: Vec<_> = vertices.iter().map(|&normal| {
+            let shade = 0.5 + 0.5 * vec3_dot(normal, light).max(0.0);
+            let shaded = [color[0] * shade, color[1] * shade, color[2] * shade, color[3]];
+            self.fill_renderer.add_vertex(vec3_add(center, vec3_scale(normal, radius)), shaded)
+        }).collect();
+        for &[a, b, c] in &triangles {
+            self.fill_renderer.add_triangle(indices[a], indices[b], indices[c]);
+        }
+    }
+
+    ///
+    /// Draw the solid screen rectangle `[x, y, width, height]`, in pixels
+    /// measured from the corner set with `set_screen_origin` as for
+    /// `draw_rect_on_screen`, blended by the color's alpha. Useful as a panel
+    /// behind screen text. Screen fills are drawn under screen lines and text,
+    /// whatever order they were queued in.
+    ///
+    pub fn draw_rect_filled_on_screen(&mut self, rect: [i32; 4], color: [f32; 4]) {
+        let origin = self.screen_origin;
+        self.fill_rect_on_screen(rect, color, origin);
+    }
+
+    ///
+    /// Draw a solid circle in the plane perpendicular to `normal`, blended by
+    /// the color's alpha
+    ///
+    pub fn draw_circle_filled(&mut self, center: [f32; 3], normal: [f32; 3], radius: f32, color: [f32; 4]) {
+        let (u, v) = plane_basis(normal);
+        let segments = self.circle_segments;
+        let rim: Vec<_> = (0..(segments + 1)).map(|i| {
+            let t = 2.0 * PI * (i as f32) / (segments as f32);
+            vec3_add(center, vec3_add(vec3_scale(u, radius * t.cos()), vec3_scale(v, radius * t.sin())))
+        }).collect();
+        self.fill_fan(center, &rim, color);
+    }
+
+    ///
+    /// Draw a solid convex polygon, fan-triangulated from its first point and
+    /// blended by the color's alpha
+    ///
+    pub fn draw_polygon_filled(&mut self, points: &[[f32; 3]], color: [f32; 4]) {
+        if points.len() < 3 {
+            return;
+        }
+        self.fill_fan(points[0], &points[1..], color);
+    }
+
+    ///
+    /// Fill the six faces of the view frustum described by `inverse_view_proj`,
+    /// blended by the color's alpha
+    ///
+    pub fn draw_frustum_filled(&mut self, inverse_view_proj: [[f32; 4]; 4], color: [f32; 4]) {
+        let corners = frustum_corners(inverse_view_proj, -1.0, 1.0);
+        self.fill_box_faces(&corners, color);
+    }
+
+    ///
+    /// Draw the points `center + axis_a * cos(t) + axis_b * sin(t)` for `t` from
+    /// `start_angle` to `end_angle`, using a share of `circle_segments`
+    /// proportional to the angle covered
+    ///
+    fn draw_elliptic_arc(
+        &mut self,
+        center: [f32; 3],
+        axis_a: [f32; 3],
+        axis_b: [f32; 3],
+        start_angle: f32,
+        end_angle: f32,
+        color: [f32; 4],
+    ) {
+        let span = end_angle - start_angle;
+        let segments = ((self.circle_segments as f32) * span.abs() / (2.0 * PI)).ceil().max(1.0) as usize;
+        let point = |t: f32| {
+            vec3_add(center, vec3_add(vec3_scale(axis_a, t.cos()), vec3_scale(axis_b, t.sin())))
+        };
+
+        let mut previous = point(start_angle);
+        for i in 1..(segments + 1) {
+            let next = point(start_angle + span * (i as f32) / (segments as f32));
+            self.draw_line(previous, next, color);
+            previous = next;
+        }
+    }
+
+    ///
+    /// Draw the edges of a box given its corners, where bit 0, 1 and 2 of a
+    /// corner's index select between the two ends of the first, second and
+    /// third box axis
+    ///
+    fn draw_box_edges(&mut self, corners: &[[f32; 3]; 8], color: [f32; 4]) {
+        for i in 0..8 {
+            for &bit in &[1, 2, 4] {
+                if i & bit == 0 {
+                    self.draw_line(corners[i], corners[i | bit], color);
+                }
+            }
+        }
+    }
+
+    ///
+    /// Draw a hemisphere over `center` bulging towards the unit vector `up`, as
+    /// its base circle plus two half circles through the pole. `u` and `v` span
+    /// the base plane.
+    ///
+    fn draw_dome(
+        &mut self,
+        center: [f32; 3],
+        up: [f32; 3],
+        u: [f32; 3],
+        v: [f32; 3],
+        radius: f32,
+        color: [f32; 4],
+    ) {
+        let up = vec3_scale(up, radius);
+        let u = vec3_scale(u, radius);
+        let v = vec3_scale(v, radius);
+        self.draw_elliptic_arc(center, u, v, 0.0, 2.0 * PI, color);
+        self.draw_elliptic_arc(center, u, up, 0.0, PI, color);
+        self.draw_elliptic_arc(center, v, up, 0.0, PI, color);
+    }
+
+    ///
+    /// Evenly spaced directions around the circle spanned by `u` and `v`, one
+    /// per side line
+    ///
+    fn side_directions(&self, u: [f32; 3], v: [f32; 3]) -> Vec<[f32; 3]> {
+        (0..self.side_lines).map(|i| {
+            let t = 2.0 * PI * (i as f32) / (self.side_lines as f32);
+            vec3_add(vec3_scale(u, t.cos()), vec3_scale(v, t.sin()))
+        }).collect()
+    }
+
+    ///
+    /// Fill the six faces of a box given its corners, indexed as for
+    /// `draw_box_edges`
+    ///
+    fn fill_box_faces(&mut self, corners: &[[f32; 3]; 8], color: [f32; 4]) {
+        let color = self.adjust_color(color);
+        let mut indices = [0; 8];
+        for (index, &corner) in indices.iter_mut().zip(corners.iter()) {
+            *index = self.fill_renderer.add_vertex(corner, color);
+        }
+
+        for &(bit, p, q) in &[(1, 2, 4), (2, 4, 1), (4, 1, 2)] {
+            for &side in &[0, bit] {
+                let face = [side, side | p, side | p | q, side | q];
+                self.fill_renderer.add_triangle(indices[face[0]], indices[face[1]], indices[face[2]]);
+                self.fill_renderer.add_triangle(indices[face[0]], indices[face[2]], indices[face[3]]);
+            }
+        }
+    }
+
+    ///
+    /// Fill the screen rectangle `[x, y, width, height]`, in pixels measured
+    /// from the `origin` corner of the screen, whatever `set_screen_origin`
+    /// was given
+    ///
+    fn fill_rect_on_screen(&mut self, rect: [i32; 4], color: [f32; 4], origin: ScreenOrigin) {
+        let [x, y, w, h] = rect;
+        let color = self.adjust_color(color);
+        let (x0, y0, x1, y1) = (x as f32, y as f32, (x + w) as f32, (y + h) as f32);
+        let a = self.fill_renderer.add_screen_vertex([x0, y0], color, origin);
+        let b = self.fill_renderer.add_screen_vertex([x0, y1], color, origin);
+        let c = self.fill_renderer.add_screen_vertex([x1, y1], color, origin);
+        let d = self.fill_renderer.add_screen_vertex([x1, y0], color, origin);
+        self.fill_renderer.add_triangle(a, b, c);
+        self.fill_renderer.add_triangle(a, c, d);
+    }
+
+    ///
+    /// Fill the triangle fan from `hub` to each consecutive pair of `rim` points
+    ///
+    fn fill_fan(&mut self, hub: [f32; 3], rim: &[[f32; 3]], color: [f32; 4]) {
+        let color = self.adjust_color(color);
+        let hub = self.fill_renderer.add_vertex(hub, color);
+        let rim: Vec<_> = rim.iter().map(|&p| self.fill_renderer.add_vertex(p, color)).collect();
+        for pair in rim.windows(2) {
+            self.fill_renderer.add_triangle(hub, pair[0], pair[1]);
+        }
+    }
+
+    ///
+    /// Draw text at a pixel position measured from the corner set with
+    /// `set_screen_origin`. Each `\n` starts a new line one font height
+    /// further down.
+    ///
     pub fn draw_text_on_screen (
         &mut self,
         text: &str,
         screen_position: [i32; 2],
         color: [f32; 4],
     ) {
-        self.text_renderer.add(text, screen_position, color);
+        self.draw_text_on_screen_with(text, screen_position, color, &TextOptions::default());
     }
 
+    ///
+    /// Draw text at a pixel position measured from the corner set with
+    /// `set_screen_origin`, laid out according to `options`
+    ///
+    pub fn draw_text_on_screen_with (
+        &mut self,
+        text: &str,
+        screen_position: [i32; 2],
+        color: [f32; 4],
+        options: &TextOptions,
+    ) {
+        let spans = parse_text(text, color, options);
+        self.queued_text.push((TextPlacement::Screen(screen_position), spans, options.clone()));
+    }
+
+    ///
+    /// Draw differently colored runs of text one after another, starting at a
+    /// pixel position measured from the corner set with `set_screen_origin`,
+    /// laid out according to `options`
+    ///
+    pub fn draw_text_spans (
+        &mut self,
+        spans: &[(&str, [f32; 4])],
+        screen_position: [i32; 2],
+        options: &TextOptions,
+    ) {
+        let spans: Vec<(&str, [f32; 4], f32)> = spans.iter().map(|&(text, color)| (text, color, 0.0)).collect();
+        self.draw_text_spans_with_offsets(&spans, screen_position, options);
+    }
+
+    ///
+    /// Like `draw_text_spans`, with each run moved the given number of pixels
+    /// down from the rest of its line, or up for negative offsets, for
+    /// subscripts and superscripts
+    ///
+    pub fn draw_text_spans_with_offsets (
+        &mut self,
+        spans: &[(&str, [f32; 4], f32)],
+        screen_position: [i32; 2],
+        options: &TextOptions,
+    ) {
+        let spans: Vec<Span> = spans.iter().map(|&(text, color, y_offset)| {
+            (text.to_string(), color, y_offset.round() as i32)
+        }).collect();
+        self.queued_text.push((TextPlacement::Screen(screen_position), spans, options.clone()));
+    }
+
+    ///
+    /// Draw text attached to a corner, edge midpoint or the center of the
+    /// screen, `offset` pixels towards the middle. The position is worked out
+    /// from the size of the render target in `render`, so it follows resizes.
+    ///
+    pub fn draw_text_anchored (
+        &mut self,
+        anchor: ScreenAnchor,
+        offset: [i32; 2],
+        text: &str,
+        color: [f32; 4],
+    ) {
+        self.draw_text_anchored_with(anchor, offset, text, color, &TextOptions::default());
+    }
+
+    ///
+    /// Like `draw_text_anchored`, laid out according to `options`. The anchor
+    /// overrides `align` and `vertical_align`.
+    ///
+    pub fn draw_text_anchored_with (
+        &mut self,
+        anchor: ScreenAnchor,
+        offset: [i32; 2],
+        text: &str,
+        color: [f32; 4],
+        options: &TextOptions,
+    ) {
+        let spans = parse_text(text, color, options);
+        self.queued_text.push((TextPlacement::Anchored(anchor, offset), spans, options.clone()));
+    }
+
+    ///
+    /// Get the width and height in pixels of a single line of text as drawn by
+    /// the text renderer's font
+    ///
+    pub fn measure_text(&self, text: &str) -> (i32, i32) {
+        self.text_renderer.measure(&self.replace_missing_glyphs(text))
+    }
+
+    ///
+    /// Get the width and height in pixels of the block of text that
+    /// `draw_text_on_screen_with` would draw with the same `options`, taking
+    /// newlines, wrapping, tabs and markup into account
+    ///
+    pub fn measure_text_block(&self, text: &str, options: &TextOptions) -> (i32, i32) {
+        let spans = self.replace_missing_glyphs_in_spans(&parse_text(text, [1.0; 4], options));
+        let line_height = self.text_renderer.measure("").1;
+        let width_of = |text: &str| self.text_renderer.measure(text).0;
+        let (_, line_rects) = layout_spans(&spans, [0, 0], line_height, options, &width_of);
+        let left = line_rects.iter().map(|rect| rect[0]).min().unwrap_or(0);
+        let right = line_rects.iter().map(|rect| rect[0] + rect[2]).max().unwrap_or(0);
+        (right - left, line_height * (line_rects.len() as i32))
+    }
+
+    ///
+    /// Get the offset in pixels from the position text was drawn at with
+    /// `draw_text_on_screen_with` and the same `options` to where a caret
+    /// after its first `char_index` characters goes, not counting markup
+    /// tags. Adding it to that position gives the position to pass
+    /// `draw_caret`, from either screen origin.
+    ///
+    pub fn caret_position(&self, text: &str, char_index: usize, options: &TextOptions) -> [i32; 2] {
+        let spans = self.replace_missing_glyphs_in_spans(&parse_text(text, [1.0; 4], options));
+        let text: String = spans.iter().map(|span| &span.0[..]).collect();
+        let line_height = self.text_renderer.measure("").1;
+        let width_of = |text: &str| self.text_renderer.measure(text).0;
+        caret_offset(&text, char_index, line_height, options, self.screen_origin, &width_of)
+    }
+
+    ///
+    /// Draw a caret as the solid screen rectangle `[x, y, 1, height]`, at a
+    /// position such as where text was drawn plus its `caret_position`, with
+    /// the height of a line of text
+    ///
+    pub fn draw_caret(&mut self, screen_position: [i32; 2], height: i32, color: [f32; 4]) {
+        self.draw_rect_filled_on_screen([screen_position[0], screen_position[1], 1, height], color);
+    }
+
+    fn replace_missing_glyphs_in_spans(&self, spans: &[Span]) -> Vec<Span> {
+        spans.iter().flat_map(|span| self.replace_missing_glyphs_in_span(span)).collect()
+    }
+
+    fn draw_spans_on_screen(&mut self, spans: &[Span], screen_position: [i32; 2], options: &TextOptions) {
+        let line_height = self.text_renderer.measure("").1;
+        let spans = self.replace_missing_glyphs_in_spans(spans);
+        let (placed, line_rects) = {
+            let width_of = |text: &str| self.text_renderer.measure(text).0;
+            layout_spans(&spans, screen_position, line_height, options, &width_of)
+        };
+
+        // The fill batch is drawn before text
+        if let Some((background_color, padding)) = options.background {
+            if let (Some(left), Some(right), Some(first), Some(last)) = (
+                line_rects.iter().map(|rect| rect[0]).min(),
+                line_rects.iter().map(|rect| rect[0] + rect[2]).max(),
+                line_rects.first(),
+                line_rects.last(),
+            ) {
+                let mut rect = [left - padding, first[1] - padding, right + padding, last[1] + last[3] + padding];
+                if let Some([clip_x, clip_y, clip_w, clip_h]) = options.clip {
+                    rect = [
+                        rect[0].max(clip_x),
+                        rect[1].max(clip_y),
+                        rect[2].min(clip_x + clip_w),
+                        rect[3].min(clip_y + clip_h),
+                    ];
+                }
+                if rect[2] > rect[0] && rect[3] > rect[1] {
+                    // Layout is always from the top left, whatever the screen origin
+                    self.fill_rect_on_screen([rect[0], rect[1], rect[2] - rect[0], rect[3] - rect[1]], background_color, ScreenOrigin::TopLeft);
+                }
+            }
+        }
+
+        // gfx_text draws glyphs in the order they were added, so anything
+        // queued first ends up behind the text itself
+        if let Some((offset, shadow_color)) = options.shadow {
+            let shadow_color = self.text_color(shadow_color);
+            for &(ref text, position, _) in &placed {
+                self.add_clipped_text(text, [position[0] + offset[0], position[1] + offset[1]], shadow_color, options.clip);
+            }
+        }
+        if let Some(outline_color) = options.outline {
+            let outline_color = self.text_color(outline_color);
+            for &(ref text, position, _) in &placed {
+                for &(dx, dy) in &[(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)] {
+                    self.add_clipped_text(text, [position[0] + dx, position[1] + dy], outline_color, options.clip);
+                }
+            }
+        }
+        for &(ref text, position, color) in &placed {
+            let color = self.text_color(color);
+            self.add_clipped_text(text, position, color, options.clip);
+        }
+    }
+
+    ///
+    /// Add a piece of a line of text to the text renderer's batch, leaving out
+    /// the characters that don't fit entirely inside `clip`
+    ///
+    fn add_clipped_text(&mut self, text: &str, position: [i32; 2], color: [f32; 4], clip: Option<[i32; 4]>) {
+        let (text, x) = match clip {
+            Some([clip_x, clip_y, clip_w, clip_h]) => {
+                let line_height = self.text_renderer.measure("").1;
+                if position[1] < clip_y || position[1] + line_height > clip_y + clip_h {
+                    return;
+                }
+                let width_of = |text: &str| self.text_renderer.measure(text).0;
+                match clip_piece(text, position[0], clip_x, clip_x + clip_w, &width_of) {
+                    Some(clipped) => clipped,
+                    None => return,
+                }
+            }
+            None => (text.to_string(), position[0]),
+        };
+        self.glyph_count += glyph_count(&text, |ch| self.is_glyph_missing(ch));
+        self.text_renderer.add(&text, [x, position[1]], color);
+    }
+
+    ///
+    /// Draw text at the point on screen a world position projects to
+    ///
     pub fn draw_text_at_position (
         &mut self,
         text: &str,
         world_position: [f32; 3],
         color: [f32; 4],
     ) {
-        self.text_renderer.add_at(text, world_position, color);
+        self.draw_text_at_position_with(text, world_position, color, &TextOptions::default());
+    }
+
+    ///
+    /// Draw text at the point on screen a world position projects to, laid
+    /// out according to `options` as `draw_text_on_screen_with` would lay it
+    /// out at that pixel. The position is projected with the matrix passed to
+    /// `render`, and text behind the viewer, or closer than the distance set
+    /// with `set_world_text_near_clip`, is left out.
+    ///
+    pub fn draw_text_at_position_with (
+        &mut self,
+        text: &str,
+        world_position: [f32; 3],
+        color: [f32; 4],
+        options: &TextOptions,
+    ) {
+        let spans = parse_text(text, color, options);
+        self.queued_text.push((TextPlacement::World(world_position), spans, options.clone()));
+    }
+
+    ///
+    /// Create the line, fill and text pipeline states ahead of time, so the
+    /// first real frame doesn't stall on shader compilation. Nothing is drawn,
+    /// and anything already queued is kept for the next `render`.
+    ///
+    pub fn warmup<C: gfx::CommandBuffer<R>, T: gfx::format::RenderFormat>(
+        &mut self,
+        encoder: &mut gfx::Encoder<R, C>,
+        color_target: &RenderTargetView<R, T>,
+        depth_target: &DepthStencilView<R, gfx::format::DepthStencil>,
+    ) -> Result<(), DebugRendererError> {
+        let queued_text = mem::take(&mut self.queued_text);
+        let lines = self.line_renderer.take_batch();
+        let fills = self.fill_renderer.take_batch();
+        let glyph_count = self.glyph_count;
+        let result = self.render(encoder, color_target, depth_target, mat4_id());
+        self.glyph_count = glyph_count;
+        self.queued_text = queued_text;
+        self.line_renderer.restore_batch(lines);
+        self.fill_renderer.restore_batch(fills);
+        result
     }
 
     pub fn render<C: gfx::CommandBuffer<R>, T: gfx::format::RenderFormat>(
@@ -98,9 +1397,69 @@ impl<R: gfx::Resources, F: Factory<R>> DebugRenderer<R, F> {
         depth_target: &DepthStencilView<R, gfx::format::DepthStencil>,
         projection: [[f32; 4]; 4],
     ) -> Result<(), DebugRendererError> {
+        let (width, height, _, _) = color_target.get_dimensions();
+        let screen_size = [width as i32, height as i32];
+        let origin = self.screen_origin;
+        self.glyph_count = 0;
+        let mut queued_text = mem::take(&mut self.queued_text);
+        if self.screen_text_on_top {
+            world_text_first(&mut queued_text);
+        }
+        for (placement, spans, mut options) in queued_text {
+            options.clip = options.clip.map(|clip| origin.rect_from_top(clip, screen_size[1]));
+            let position = match placement {
+                TextPlacement::Screen(position) => {
+                    options.vertical_align = origin.vertical_align_from_top(options.vertical_align);
+                    [position[0], origin.y_from_top(position[1], screen_size[1])]
+                }
+                TextPlacement::Anchored(anchor, offset) => {
+                    let (position, align, vertical_align) = anchor.resolve(offset, screen_size);
+                    options.align = align;
+                    options.vertical_align = vertical_align;
+                    position
+                }
+                TextPlacement::World(world_position) => {
+                    match project_to_screen(projection, world_position, screen_size, self.world_text_near_clip) {
+                        Some(position) => to_pixel(position, self.pixel_snap),
+                        None => continue,
+                    }
+                }
+            };
+            self.draw_spans_on_screen(&spans, position, &options);
+        }
+
         self.line_renderer.render(encoder, &mut self.factory,
-            color_target, depth_target, projection)?;
+            color_target, depth_target, projection, self.global_alpha)?;
+        self.fill_renderer.render(encoder, &mut self.factory,
+            color_target, depth_target, projection, self.global_alpha)?;
         self.text_renderer.draw_at(encoder, color_target, projection)?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn font_errors_have_a_readable_message() {
+        let err = DebugRendererError::from(gfx_text::Error::from(gfx_text::FontError::EmptyFont));
+        assert_eq!(err.to_string(), "Text renderer error: FontError(EmptyFont)");
+        assert!(err.source().is_none());
+    }
+
+    #[test]
+    fn world_text_first_keeps_the_order_within_each_kind() {
+        let queued = |placement, text: &str| (placement, vec![(text.to_string(), [1.0; 4], 0)], TextOptions::default());
+        let mut queued_text = vec![
+            queued(TextPlacement::Screen([0, 0]), "screen 1"),
+            queued(TextPlacement::World([0.0; 3]), "world 1"),
+            queued(TextPlacement::Anchored(ScreenAnchor::Top, [0, 0]), "anchored"),
+            queued(TextPlacement::World([1.0; 3]), "world 2"),
+            queued(TextPlacement::Screen([1, 1]), "screen 2"),
+        ];
+        world_text_first(&mut queued_text);
+        let order: Vec<_> = queued_text.iter().map(|entry| &entry.1[0].0[..]).collect();
+        assert_eq!(order, vec!["world 1", "world 2", "screen 1", "anchored", "screen 2"]);
+    }
+}
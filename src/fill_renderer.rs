@@ -0,0 +1,204 @@
+use gfx;
+use gfx::handle::{DepthStencilView, RenderTargetView};
+use gfx::traits::*;
+use gfx::PipelineState;
+use std::collections::hash_map::{Entry, HashMap};
+use std::mem;
+
+use shaders::{VertexSpace, FRAGMENT_SRC, SCREEN_FILL_DEPTH, VERTEX_SRC};
+use text_layout::ScreenOrigin;
+use utils::{grow_buffer, scale_alpha};
+use DebugRendererError;
+
+type IndexT = u32;
+
+pub struct FillRenderer<R: gfx::Resources> {
+    vertex_data: Vec<Vertex>,
+    vertex_buffer: gfx::handle::Buffer<R, Vertex>,
+    index_data: Vec<IndexT>,
+    index_buffer: gfx::handle::Buffer<R, IndexT>,
+    pso_map: HashMap<gfx::format::Format, PipelineState<R, pipe::Meta>>,
+    shaders: gfx::ShaderSet<R>,
+}
+
+impl<R: gfx::Resources> FillRenderer<R> {
+
+    pub fn new<F: gfx::Factory<R>>(
+        factory: &mut F,
+        initial_buffer_size: usize
+    ) -> FillRenderer<R> {
+
+        let set = factory.create_shader_set(VERTEX_SRC[1], FRAGMENT_SRC[1]).unwrap();
+        let vertex_buffer = factory.create_buffer(
+            initial_buffer_size,
+            gfx::buffer::Role::Vertex,
+            gfx::memory::Usage::Dynamic,
+            gfx::memory::Bind::empty()
+        ).expect("Could not create vertex buffer");
+        let index_buffer = factory.create_buffer(
+            initial_buffer_size,
+            gfx::buffer::Role::Index,
+            gfx::memory::Usage::Dynamic,
+            gfx::memory::Bind::empty()
+        ).expect("Could not create index buffer");
+
+        FillRenderer {
+            vertex_data: Vec::new(),
+            vertex_buffer,
+            index_data: Vec::new(),
+            index_buffer,
+            pso_map: HashMap::new(),
+            shaders: set,
+        }
+    }
+
+    fn prepare_pso<F: gfx::Factory<R>>(&mut self, factory: &mut F, format: gfx::format::Format) -> Result<(), gfx::PipelineStateError<String>> {
+        if let Entry::Vacant(e) = self.pso_map.entry(format) {
+            let init = pipe::Init {
+                vbuf: (),
+                u_model_view_proj: "u_model_view_proj",
+                u_screen_size: "u_screen_size",
+                out_color: ("o_Color", format, gfx::state::ColorMask::all(), Some(gfx::preset::blend::ALPHA)),
+                // Test against, but don't write, depth so translucent faces don't hide each other
+                out_depth: gfx::preset::depth::LESS_EQUAL_TEST,
+            };
+            let pso = factory.create_pipeline_state(
+                &self.shaders,
+                gfx::Primitive::TriangleList,
+                gfx::state::Rasterizer::new_fill(),
+                init
+            )?;
+            e.insert(pso);
+        }
+        Ok(())
+    }
+
+    ///
+    /// Add a world-space vertex to the batch, returning its index for use
+    /// with 'add_triangle'
+    ///
+    pub fn add_vertex(&mut self, position: [f32; 3], color: [f32; 4]) -> IndexT {
+        self.vertex_data.push(Vertex{position, color, screen_rel: VertexSpace::World.shader_value()});
+        (self.vertex_data.len() - 1) as IndexT
+    }
+
+    ///
+    /// Add a vertex in pixel coordinates relative to the `origin` corner of
+    /// the screen to the batch, returning its index for use with
+    /// 'add_triangle'
+    ///
+    pub fn add_screen_vertex(&mut self, position: [f32; 2], color: [f32; 4], origin: ScreenOrigin) -> IndexT {
+        let screen_rel = VertexSpace::Screen(origin).shader_value();
+        self.vertex_data.push(Vertex{position: [position[0], position[1], SCREEN_FILL_DEPTH], color, screen_rel});
+        (self.vertex_data.len() - 1) as IndexT
+    }
+
+    ///
+    /// Add a triangle between three vertices already in the batch
+    ///
+    pub fn add_triangle(&mut self, a: IndexT, b: IndexT, c: IndexT) {
+        self.index_data.push(a);
+        self.index_data.push(b);
+        self.index_data.push(c);
+    }
+
+    ///
+    /// Add a solid triangle to the batch to be drawn on 'render'
+    ///
+    pub fn draw_triangle(&mut self, a: [f32; 3], b: [f32; 3], c: [f32; 3], color: [f32; 4]) {
+        let a = self.add_vertex(a, color);
+        let b = self.add_vertex(b, color);
+        let c = self.add_vertex(c, color);
+        self.add_triangle(a, b, c);
+    }
+
+    ///
+    /// Remove the current batch of vertices and triangles, to be put back with
+    /// 'restore_batch'
+    ///
+    pub fn take_batch(&mut self) -> (Vec<Vertex>, Vec<IndexT>) {
+        (mem::take(&mut self.vertex_data), mem::take(&mut self.index_data))
+    }
+
+    ///
+    /// Replace the current batch with one from 'take_batch'
+    ///
+    pub fn restore_batch(&mut self, batch: (Vec<Vertex>, Vec<IndexT>)) {
+        self.vertex_data = batch.0;
+        self.index_data = batch.1;
+    }
+
+    ///
+    /// Draw and clear the current batch of triangles, with the alpha of every
+    /// vertex multiplied by `alpha`
+    ///
+    pub fn render<C: gfx::CommandBuffer<R>, F: gfx::Factory<R>, T: gfx::format::RenderFormat> (
+        &mut self,
+        encoder: &mut gfx::Encoder<R, C>,
+        factory: &mut F,
+        color_target: &RenderTargetView<R, T>,
+        depth_target: &DepthStencilView<R, gfx::format::DepthStencil>,
+        projection: [[f32; 4]; 4],
+        alpha: f32,
+    ) -> Result<(), DebugRendererError> {
+        use gfx::memory::Typed;
+
+        if alpha != 1.0 {
+            for vertex in &mut self.vertex_data {
+                vertex.color = scale_alpha(vertex.color, alpha);
+            }
+        }
+
+        if self.vertex_data.len() > self.vertex_buffer.len() {
+            self.vertex_buffer = grow_buffer(factory, &self.vertex_buffer, gfx::buffer::Role::Vertex, self.vertex_data.len());
+        }
+        if self.index_data.len() > self.index_buffer.len() {
+            self.index_buffer = grow_buffer(factory, &self.index_buffer, gfx::buffer::Role::Index, self.index_data.len());
+        }
+
+        encoder.update_buffer(&self.vertex_buffer, &self.vertex_data[..], 0)?;
+        encoder.update_buffer(&self.index_buffer, &self.index_data[..], 0)?;
+
+        self.prepare_pso(factory, T::get_format())?;
+        let pso = &self.pso_map[&T::get_format()];
+
+        let data = pipe::Data {
+            vbuf: self.vertex_buffer.clone(),
+            u_model_view_proj: projection,
+            u_screen_size: {
+                let (w, h, _, _) = color_target.get_dimensions();
+                [w as f32, h as f32]
+            },
+            out_color: color_target.raw().clone(),
+            out_depth: depth_target.clone(),
+        };
+
+        let slice = gfx::Slice {
+            start: 0,
+            end: self.index_data.len() as u32,
+            base_vertex: 0,
+            instances: None,
+            buffer: gfx::IndexBuffer::Index32(self.index_buffer.clone()),
+        };
+        encoder.draw(&slice, pso, &data);
+
+        self.vertex_data.clear();
+        self.index_data.clear();
+
+        Ok(())
+    }
+}
+
+gfx_vertex_struct!( Vertex {
+    position: [f32; 3] = "at_position",
+    color: [f32; 4] = "at_color",
+    screen_rel: i32 = "at_screen_rel",
+});
+
+gfx_pipeline_base!( pipe {
+    vbuf: gfx::VertexBuffer<Vertex>,
+    u_model_view_proj: gfx::Global<[[f32; 4]; 4]>,
+    u_screen_size: gfx::Global<[f32; 2]>,
+    out_color: gfx::RawRenderTarget,
+    out_depth: gfx::DepthTarget<::gfx::format::DepthStencil>,
+});
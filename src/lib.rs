@@ -4,8 +4,18 @@ extern crate gfx_text;
 extern crate vecmath;
 
 mod debug_renderer;
+mod fill_renderer;
 mod line_renderer;
+mod shaders;
+mod text_layout;
 mod utils;
 
 pub use debug_renderer::{DebugRenderer,
                          DebugRendererError};
+pub use text_layout::{layout_text,
+                      PlacedText,
+                      ScreenAnchor,
+                      ScreenOrigin,
+                      TextAlign,
+                      TextOptions,
+                      TextVerticalAlign};
@@ -3,8 +3,11 @@ use gfx::handle::{DepthStencilView, RenderTargetView};
 use gfx::traits::*;
 use gfx::PipelineState;
 use std::collections::hash_map::{Entry, HashMap};
+use std::mem;
 
-use utils::grow_buffer;
+use shaders::{VertexSpace, FRAGMENT_SRC, SCREEN_LINE_DEPTH, VERTEX_SRC};
+use text_layout::ScreenOrigin;
+use utils::{grow_buffer, scale_alpha};
 use DebugRendererError;
 
 pub struct LineRenderer<R: gfx::Resources> {
@@ -37,7 +40,7 @@ impl<R: gfx::Resources> LineRenderer<R> {
         };
         */
 
-        let set = factory.create_shader_set(&VERTEX_SRC[1], &FRAGMENT_SRC[1]).unwrap();
+        let set = factory.create_shader_set(VERTEX_SRC[1], FRAGMENT_SRC[1]).unwrap();
         let vertex_buffer = factory.create_buffer(
             initial_buffer_size,
             gfx::buffer::Role::Vertex,
@@ -47,17 +50,18 @@ impl<R: gfx::Resources> LineRenderer<R> {
 
         LineRenderer {
             vertex_data: Vec::new(),
-            vertex_buffer: vertex_buffer,
+            vertex_buffer,
             pso_map: HashMap::new(),
             shaders: set,
         }
     }
 
     fn prepare_pso<F: gfx::Factory<R>>(&mut self, factory: &mut F, format: gfx::format::Format) -> Result<(), gfx::PipelineStateError<String>> {
-        Ok(if let Entry::Vacant(e) = self.pso_map.entry(format) {
+        if let Entry::Vacant(e) = self.pso_map.entry(format) {
             let init = pipe::Init {
                 vbuf: (),
                 u_model_view_proj: "u_model_view_proj",
+                u_screen_size: "u_screen_size",
                 out_color: ("o_Color", format, gfx::state::ColorMask::all(), Some(gfx::preset::blend::ALPHA)),
                 out_depth: gfx::preset::depth::LESS_EQUAL_WRITE,
             };
@@ -68,19 +72,46 @@ impl<R: gfx::Resources> LineRenderer<R> {
                 init
             )?;
             e.insert(pso);
-        })
+        }
+        Ok(())
     }
 
     ///
     /// Add a line to the batch to be drawn on 'render'
     ///
     pub fn draw_line(&mut self, start: [f32; 3], end: [f32; 3], color: [f32; 4]) {
-        self.vertex_data.push(Vertex{position: start, color: color});
-        self.vertex_data.push(Vertex{position: end, color: color});
+        let screen_rel = VertexSpace::World.shader_value();
+        self.vertex_data.push(Vertex{position: start, color, screen_rel});
+        self.vertex_data.push(Vertex{position: end, color, screen_rel});
+    }
+
+    ///
+    /// Add a line in pixel coordinates relative to the `origin` corner of the
+    /// screen to the batch to be drawn on 'render'
+    ///
+    pub fn draw_line_on_screen(&mut self, start: [f32; 2], end: [f32; 2], color: [f32; 4], origin: ScreenOrigin) {
+        let screen_rel = VertexSpace::Screen(origin).shader_value();
+        self.vertex_data.push(Vertex{position: [start[0], start[1], SCREEN_LINE_DEPTH], color, screen_rel});
+        self.vertex_data.push(Vertex{position: [end[0], end[1], SCREEN_LINE_DEPTH], color, screen_rel});
+    }
+
+    ///
+    /// Remove the current batch of lines, to be put back with 'restore_batch'
+    ///
+    pub fn take_batch(&mut self) -> Vec<Vertex> {
+        mem::take(&mut self.vertex_data)
+    }
+
+    ///
+    /// Replace the current batch of lines with one from 'take_batch'
+    ///
+    pub fn restore_batch(&mut self, batch: Vec<Vertex>) {
+        self.vertex_data = batch;
     }
 
     ///
-    /// Draw and clear the current batch of lines
+    /// Draw and clear the current batch of lines, with the alpha of every
+    /// vertex multiplied by `alpha`
     ///
     pub fn render<C: gfx::CommandBuffer<R>, F: gfx::Factory<R>, T: gfx::format::RenderFormat> (
         &mut self,
@@ -89,9 +120,16 @@ impl<R: gfx::Resources> LineRenderer<R> {
         color_target: &RenderTargetView<R, T>,
         depth_target: &DepthStencilView<R, gfx::format::DepthStencil>,
         projection: [[f32; 4]; 4],
+        alpha: f32,
     ) -> Result<(), DebugRendererError> {
         use gfx::memory::Typed;
 
+        if alpha != 1.0 {
+            for vertex in &mut self.vertex_data {
+                vertex.color = scale_alpha(vertex.color, alpha);
+            }
+        }
+
         if self.vertex_data.len() > self.vertex_buffer.len() {
             self.vertex_buffer = grow_buffer(factory, &self.vertex_buffer, gfx::buffer::Role::Vertex, self.vertex_data.len());
         }
@@ -104,11 +142,17 @@ impl<R: gfx::Resources> LineRenderer<R> {
         let data = pipe::Data {
             vbuf: self.vertex_buffer.clone(),
             u_model_view_proj: projection,
+            u_screen_size: {
+                let (w, h, _, _) = color_target.get_dimensions();
+                [w as f32, h as f32]
+            },
             out_color: color_target.raw().clone(),
             out_depth: depth_target.clone(),
         };
 
-        let slice = gfx::Slice::new_match_vertex_buffer(&self.vertex_buffer);
+        // Only draw the vertices queued this frame, not the whole (possibly larger) buffer
+        let mut slice = gfx::Slice::new_match_vertex_buffer(&self.vertex_buffer);
+        slice.end = self.vertex_data.len() as u32;
         encoder.draw(&slice, pso, &data);
 
         self.vertex_data.clear();
@@ -117,63 +161,16 @@ impl<R: gfx::Resources> LineRenderer<R> {
     }
 }
 
-static VERTEX_SRC: [&'static [u8]; 2] = [
-b"
-    #version 120
-
-    uniform mat4 u_model_view_proj;
-    attribute vec3 at_position;
-    attribute vec4 at_color;
-    varying vec4 v_color;
-
-    void main() {
-        gl_Position = u_model_view_proj * vec4(at_position, 1.0);
-        v_color = at_color;
-    }
-",
-b"
-    #version 150 core
-
-    uniform mat4 u_model_view_proj;
-    in vec3 at_position;
-    in vec4 at_color;
-    out vec4 v_color;
-
-    void main() {
-        gl_Position = u_model_view_proj * vec4(at_position, 1.0);
-        v_color = at_color;
-    }
-"];
-
-static FRAGMENT_SRC: [&'static [u8]; 2] = [
-b"
-    #version 120
-
-    varying vec4 v_color;
-
-    void main() {
-        gl_FragColor = v_color;
-    }
-",
-b"
-    #version 150
-
-    in vec4 v_color;
-    out vec4 out_color;
-
-    void main() {
-        out_color = v_color;
-    }
-"];
-
 gfx_vertex_struct!( Vertex {
     position: [f32; 3] = "at_position",
     color: [f32; 4] = "at_color",
+    screen_rel: i32 = "at_screen_rel",
 });
 
 gfx_pipeline_base!( pipe {
     vbuf: gfx::VertexBuffer<Vertex>,
     u_model_view_proj: gfx::Global<[[f32; 4]; 4]>,
+    u_screen_size: gfx::Global<[f32; 2]>,
     out_color: gfx::RawRenderTarget,
     out_depth: gfx::DepthTarget<::gfx::format::DepthStencil>,
 });
@@ -0,0 +1,122 @@
+use text_layout::ScreenOrigin;
+
+///
+/// NDC depth of screen-relative lines, in front of everything in the scene
+///
+pub const SCREEN_LINE_DEPTH: f32 = -1.0;
+
+///
+/// NDC depth of screen-relative fills, just behind screen-relative lines so
+/// that depth testing keeps panels from covering lines drawn on them
+///
+pub const SCREEN_FILL_DEPTH: f32 = -0.999;
+
+///
+/// The space the position of a line or fill vertex is given in
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VertexSpace {
+    /// World space, transformed by the matrix passed to `render`
+    World,
+    /// Pixels from the given corner of the screen
+    Screen(ScreenOrigin),
+}
+
+impl VertexSpace {
+    ///
+    /// The `at_screen_rel` value that `VERTEX_SRC` expects for this space
+    ///
+    pub fn shader_value(self) -> i32 {
+        match self {
+            VertexSpace::World => 0,
+            VertexSpace::Screen(ScreenOrigin::TopLeft) => 1,
+            VertexSpace::Screen(ScreenOrigin::BottomLeft) => 2,
+        }
+    }
+}
+
+///
+/// GLSL 1.20 and 1.50 vertex shaders shared by the line and fill renderers.
+/// Vertices are in world space, transformed by `u_model_view_proj`, or in
+/// pixels from the top left corner of the screen when `at_screen_rel` is 1,
+/// or from the bottom left corner when it is 2, with z giving the NDC depth
+/// directly.
+///
+pub static VERTEX_SRC: [&[u8]; 2] = [
+b"
+    #version 120
+
+    uniform mat4 u_model_view_proj;
+    uniform vec2 u_screen_size;
+    attribute vec3 at_position;
+    attribute vec4 at_color;
+    attribute float at_screen_rel;
+    varying vec4 v_color;
+
+    void main() {
+        if (at_screen_rel > 0.5) {
+            vec2 ndc = at_position.xy / u_screen_size * 2.0 - 1.0;
+            gl_Position = vec4(ndc.x, at_screen_rel > 1.5 ? ndc.y : -ndc.y, at_position.z, 1.0);
+        } else {
+            gl_Position = u_model_view_proj * vec4(at_position, 1.0);
+        }
+        v_color = at_color;
+    }
+",
+b"
+    #version 150 core
+
+    uniform mat4 u_model_view_proj;
+    uniform vec2 u_screen_size;
+    in vec3 at_position;
+    in vec4 at_color;
+    in int at_screen_rel;
+    out vec4 v_color;
+
+    void main() {
+        if (at_screen_rel != 0) {
+            // Pixel coordinates from the top or bottom left corner, with z the NDC depth
+            vec2 ndc = at_position.xy / u_screen_size * 2.0 - 1.0;
+            gl_Position = vec4(ndc.x, at_screen_rel == 2 ? ndc.y : -ndc.y, at_position.z, 1.0);
+        } else {
+            gl_Position = u_model_view_proj * vec4(at_position, 1.0);
+        }
+        v_color = at_color;
+    }
+"];
+
+///
+/// Fragment shaders matching `VERTEX_SRC`
+///
+pub static FRAGMENT_SRC: [&[u8]; 2] = [
+b"
+    #version 120
+
+    varying vec4 v_color;
+
+    void main() {
+        gl_FragColor = v_color;
+    }
+",
+b"
+    #version 150
+
+    in vec4 v_color;
+    out vec4 out_color;
+
+    void main() {
+        out_color = v_color;
+    }
+"];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vertex_spaces_map_to_the_shader_values() {
+        assert_eq!(VertexSpace::World.shader_value(), 0);
+        assert_eq!(VertexSpace::Screen(ScreenOrigin::TopLeft).shader_value(), 1);
+        assert_eq!(VertexSpace::Screen(ScreenOrigin::BottomLeft).shader_value(), 2);
+    }
+}
@@ -0,0 +1,954 @@
+use std::mem;
+use std::ops::Range;
+
+///
+/// A run of text drawn in one color, moved the given number of pixels down
+/// from the rest of its line
+///
+pub type Span = (String, [f32; 4], i32);
+
+///
+/// A laid out line of text: its spans, its width, and whether it continues
+/// the line before it after a soft wrap
+///
+pub type Line = (Vec<Span>, i32, bool);
+
+///
+/// Horizontal alignment of each line of text relative to its x position
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+    /// Left aligned, with the spaces of every line that wrapping breaks
+    /// before its end stretched so that it fills `TextOptions::max_width`
+    Justify,
+}
+
+///
+/// Which part of a block of text is placed at its y position
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TextVerticalAlign {
+    #[default]
+    Top,
+    Center,
+    Bottom,
+}
+
+///
+/// A point on the edge or at the center of the screen that text can be
+/// attached to with `DebugRenderer::draw_text_anchored`
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScreenAnchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl ScreenAnchor {
+    ///
+    /// The pixel position of the anchor on a screen of the given size, moved
+    /// `offset` pixels towards the middle of the screen, along with the
+    /// alignment that keeps text attached there on screen
+    ///
+    pub fn resolve(self, offset: [i32; 2], screen_size: [i32; 2]) -> ([i32; 2], TextAlign, TextVerticalAlign) {
+        let (align, x) = match self {
+            ScreenAnchor::TopLeft | ScreenAnchor::Left | ScreenAnchor::BottomLeft =>
+                (TextAlign::Left, offset[0]),
+            ScreenAnchor::Top | ScreenAnchor::Center | ScreenAnchor::Bottom =>
+                (TextAlign::Center, screen_size[0] / 2 + offset[0]),
+            ScreenAnchor::TopRight | ScreenAnchor::Right | ScreenAnchor::BottomRight =>
+                (TextAlign::Right, screen_size[0] - offset[0]),
+        };
+        let (vertical_align, y) = match self {
+            ScreenAnchor::TopLeft | ScreenAnchor::Top | ScreenAnchor::TopRight =>
+                (TextVerticalAlign::Top, offset[1]),
+            ScreenAnchor::Left | ScreenAnchor::Center | ScreenAnchor::Right =>
+                (TextVerticalAlign::Center, screen_size[1] / 2 + offset[1]),
+            ScreenAnchor::BottomLeft | ScreenAnchor::Bottom | ScreenAnchor::BottomRight =>
+                (TextVerticalAlign::Bottom, screen_size[1] - offset[1]),
+        };
+        ([x, y], align, vertical_align)
+    }
+}
+
+///
+/// The corner of the screen that pixel positions passed to the screen-space
+/// draw methods are measured from
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ScreenOrigin {
+    /// y grows downwards from the top left corner
+    #[default]
+    TopLeft,
+    /// y grows upwards from the bottom left corner
+    BottomLeft,
+}
+
+impl ScreenOrigin {
+    ///
+    /// Convert a y position measured from this origin to one measured from the
+    /// top of a screen `screen_height` pixels high
+    ///
+    pub fn y_from_top(self, y: i32, screen_height: i32) -> i32 {
+        match self {
+            ScreenOrigin::TopLeft => y,
+            ScreenOrigin::BottomLeft => screen_height - y,
+        }
+    }
+
+    ///
+    /// Convert a rectangle `[x, y, width, height]` measured from this origin,
+    /// so with `y` its bottom edge for `BottomLeft`, to one measured from the
+    /// top of a screen `screen_height` pixels high
+    ///
+    pub fn rect_from_top(self, rect: [i32; 4], screen_height: i32) -> [i32; 4] {
+        match self {
+            ScreenOrigin::TopLeft => rect,
+            ScreenOrigin::BottomLeft => [rect[0], screen_height - rect[1] - rect[3], rect[2], rect[3]],
+        }
+    }
+
+    ///
+    /// The vertical alignment as seen from the top that matches
+    /// `vertical_align` given from this origin. Alignment is mirrored along
+    /// with y, so from the bottom left text extends upwards from its position
+    /// by default.
+    ///
+    pub fn vertical_align_from_top(self, vertical_align: TextVerticalAlign) -> TextVerticalAlign {
+        match (self, vertical_align) {
+            (ScreenOrigin::BottomLeft, TextVerticalAlign::Top) => TextVerticalAlign::Bottom,
+            (ScreenOrigin::BottomLeft, TextVerticalAlign::Bottom) => TextVerticalAlign::Top,
+            (_, vertical_align) => vertical_align,
+        }
+    }
+}
+
+///
+/// Layout options for `DebugRenderer::draw_text_on_screen_with`. The default
+/// matches `draw_text_on_screen`.
+///
+#[derive(Clone, Debug, Default)]
+pub struct TextOptions {
+    /// Wrap words onto new lines so that no line is wider than this many
+    /// pixels. A single word wider than this is left on its own line.
+    pub max_width: Option<i32>,
+    /// Which part of each line is placed at the given x position
+    pub align: TextAlign,
+    /// Which part of the whole block of lines is placed at the given y
+    /// position. Together with `align` this anchors the text at any corner,
+    /// edge midpoint or its center.
+    pub vertical_align: TextVerticalAlign,
+    /// Draw a copy of the text offset by `[x, y]` pixels in the given color
+    /// behind it, e.g. `Some(([1, 1], [0.0, 0.0, 0.0, 0.8]))`
+    pub shadow: Option<([i32; 2], [f32; 4])>,
+    /// Draw a one pixel outline of the given color around the glyphs, made of
+    /// eight offset copies behind the text
+    pub outline: Option<[f32; 4]>,
+    /// Fill the measured extent of the text, grown by the given padding in
+    /// pixels, with a color behind it, e.g. `Some(([0.0, 0.0, 0.0, 0.6], 4))`.
+    /// For world text the box is placed around the projected label.
+    pub background: Option<([f32; 4], i32)>,
+    /// Interpret inline color tags: `{#rrggbb}`, `{#rrggbbaa}` or a color name
+    /// such as `[red]`, `[yellow]` or `[gray]` switches color, and `{/}` or
+    /// `[/]` switches back. Other text in braces or brackets is drawn as is.
+    pub markup: bool,
+    /// Move text after each `\t` to the next multiple of this many pixels
+    /// from the start of the line. Without it tabs are left to the font, which
+    /// usually has no glyph for them.
+    pub tab_width: Option<i32>,
+    /// Indent every line after the first that wrapping breaks off a longer
+    /// line by this many pixels, within `max_width`. Only left aligned and
+    /// justified text is indented.
+    pub wrap_indent: i32,
+    /// Only draw text inside the screen rectangle `[x, y, width, height]`.
+    /// Glyphs cannot be cut, so lines and characters that don't fit entirely
+    /// inside are left out.
+    pub clip: Option<[i32; 4]>,
+}
+
+impl TextOptions {
+    ///
+    /// The indent of lines continued after a soft wrap, which is `wrap_indent`
+    /// for left aligned and justified text and nothing otherwise
+    ///
+    pub fn continuation_indent(&self) -> i32 {
+        match self.align {
+            TextAlign::Left | TextAlign::Justify => self.wrap_indent,
+            _ => 0,
+        }
+    }
+}
+
+///
+/// The x position at which to start a line `width` pixels wide so that it is
+/// aligned to `x`
+///
+pub fn align_line(x: i32, width: i32, align: TextAlign) -> i32 {
+    match align {
+        TextAlign::Left | TextAlign::Justify => x,
+        TextAlign::Center => x - width / 2,
+        TextAlign::Right => x - width,
+    }
+}
+
+///
+/// The y position of the top of a block of text `height` pixels high so that
+/// it is aligned to `y`
+///
+pub fn align_block(y: i32, height: i32, vertical_align: TextVerticalAlign) -> i32 {
+    match vertical_align {
+        TextVerticalAlign::Top => y,
+        TextVerticalAlign::Center => y - height / 2,
+        TextVerticalAlign::Bottom => y - height,
+    }
+}
+
+///
+/// How far the pen moves after drawing `text`. `measure` reports the extent of
+/// the glyphs, which for the last glyph can differ from its advance, but the
+/// difference cancels out when measuring the text twice over.
+///
+pub fn advance<M: Fn(&str) -> i32>(text: &str, width_of: M) -> i32 {
+    width_of(&format!("{}{}", text, text)) - width_of(text)
+}
+
+///
+/// Split `text` at tabs into pieces, each with the x offset from the start of
+/// the line it should be drawn at, given the pen offset `pen` the text starts
+/// at. Also returns the pen offset after the text.
+///
+pub fn expand_tabs<'a, M: Fn(&str) -> i32>(
+    text: &'a str,
+    pen: i32,
+    tab_width: Option<i32>,
+    width_of: &M,
+) -> (Vec<(&'a str, i32)>, i32) {
+    let tab_width = match tab_width {
+        Some(tab_width) if tab_width > 0 => tab_width,
+        _ => return (vec![(text, pen)], pen + advance(text, width_of)),
+    };
+
+    let mut pieces = Vec::new();
+    let mut pen = pen;
+    for (i, piece) in text.split('\t').enumerate() {
+        if i > 0 {
+            pen = (pen / tab_width + 1) * tab_width;
+        }
+        if !piece.is_empty() {
+            pieces.push((piece, pen));
+            pen += advance(piece, width_of);
+        }
+    }
+    (pieces, pen)
+}
+
+///
+/// The drawn width of a single line of text, taking tab stops into account
+///
+pub fn line_width<M: Fn(&str) -> i32>(text: &str, tab_width: Option<i32>, width_of: &M) -> i32 {
+    let (pieces, _) = expand_tabs(text, 0, tab_width, width_of);
+    pieces.iter().map(|&(piece, x)| x + width_of(piece)).max().unwrap_or(0)
+}
+
+///
+/// The offset from the position `text` is drawn at to where a caret placed
+/// after its first `char_index` characters goes, for text laid out according
+/// to `options` with lines `line_height` pixels apart. The offset is to the
+/// top of the caret's line from `ScreenOrigin::TopLeft`, and to its bottom
+/// from `ScreenOrigin::BottomLeft`, so that the caret is the screen rectangle
+/// `[x, y, 1, line_height]` measured from `origin` either way. A caret in the
+/// spaces left out at a wrap goes at the end of the line before.
+///
+pub fn caret_offset<M: Fn(&str) -> i32>(
+    text: &str,
+    char_index: usize,
+    line_height: i32,
+    options: &TextOptions,
+    origin: ScreenOrigin,
+    width_of: &M,
+) -> [i32; 2] {
+    let end = text.char_indices().nth(char_index).map_or(text.len(), |(i, _)| i);
+    let tab_width = options.tab_width;
+    let measure = |text: &str| line_width(text, tab_width, width_of);
+    let lines = break_lines(text, options.max_width, options.continuation_indent(), measure);
+
+    let index = lines.iter().rposition(|line| line.0.start <= end).unwrap_or(0);
+    let (ref line, soft) = lines[index];
+    let line_text = &text[line.clone()];
+    let before = &text[line.start..end.min(line.end)];
+    let (_, pen) = expand_tabs(before, 0, tab_width, width_of);
+
+    // Follow the alignment, indent and justification of `place_lines`
+    let width = measure(line_text);
+    let indent = if soft { options.continuation_indent() } else { 0 };
+    let gaps = line_text.matches(' ').count() as i32;
+    let wrapped = lines.get(index + 1).is_some_and(|next| next.1);
+    let extra = match (options.align, options.max_width) {
+        (TextAlign::Justify, Some(max_width)) if wrapped && gaps > 0 => (max_width - indent - width).max(0),
+        _ => 0,
+    };
+    let spaces = before.matches(' ').count() as i32;
+    let x = align_line(0, width, options.align) + indent + pen + extra * spaces / gaps.max(1);
+
+    let block_height = line_height * (lines.len() as i32);
+    let vertical_align = origin.vertical_align_from_top(options.vertical_align);
+    let top = align_block(0, block_height, vertical_align) + line_height * (index as i32);
+    match origin {
+        ScreenOrigin::TopLeft => [x, top],
+        ScreenOrigin::BottomLeft => [x, -(top + line_height)],
+    }
+}
+
+///
+/// Trim `text`, drawn starting at `x`, down to the characters lying entirely
+/// between `left` and `right`, returning them and the x to draw them at
+///
+pub fn clip_piece<M: Fn(&str) -> i32>(
+    text: &str,
+    x: i32,
+    left: i32,
+    right: i32,
+    width_of: &M,
+) -> Option<(String, i32)> {
+    let mut kept = String::new();
+    let mut kept_x = x;
+    let mut pen = x;
+    let mut buffer = [0; 4];
+    for ch in text.chars() {
+        let ch = ch.encode_utf8(&mut buffer);
+        let end = pen + width_of(ch);
+        if pen >= left && end <= right {
+            if kept.is_empty() {
+                kept_x = pen;
+            }
+            kept.push_str(ch);
+        }
+        pen += advance(ch, width_of);
+    }
+    if kept.is_empty() { None } else { Some((kept, kept_x)) }
+}
+
+///
+/// Break `text` into lines at each `\n` and, if `max_width` is given, between
+/// words, measuring candidate lines with `width_of`. Spaces at a break
+/// between words are left out of both lines, and lines after such a break
+/// have to fit in `max_width - wrap_indent`. Lines are returned as byte
+/// ranges into `text`, each with whether it starts at a break between words.
+///
+pub fn break_lines<M: Fn(&str) -> i32>(
+    text: &str,
+    max_width: Option<i32>,
+    wrap_indent: i32,
+    width_of: M,
+) -> Vec<(Range<usize>, bool)> {
+    let mut lines = Vec::new();
+    let mut hard_start = 0;
+    for hard_line in text.split('\n') {
+        let hard_end = hard_start + hard_line.len();
+        match max_width {
+            None => lines.push((hard_start..hard_end, false)),
+            Some(max_width) => {
+                let mut line_start = hard_start;
+                let mut line_end = None;
+                let mut soft = false;
+                let mut word_start = hard_start;
+                for word in hard_line.split(' ') {
+                    let word_end = word_start + word.len();
+                    if !word.is_empty() {
+                        if let Some(end) = line_end {
+                            let available = if soft { max_width - wrap_indent } else { max_width };
+                            if width_of(&text[line_start..word_end]) > available {
+                                // Break in the spaces before the word, leaving them out of both lines
+                                lines.push((line_start..end, soft));
+                                line_start = word_start;
+                                soft = true;
+                            }
+                        }
+                        line_end = Some(word_end);
+                    }
+                    word_start = word_end + 1;
+                }
+                lines.push((line_start..hard_end, soft));
+            }
+        }
+        hard_start = hard_end + 1;
+    }
+    lines
+}
+
+///
+/// Lay out a sequence of colored spans as one text, breaking it into lines as
+/// `break_lines` does and splitting the spans at the line breaks
+///
+pub fn wrap_spans<M: Fn(&str) -> i32>(
+    spans: &[Span],
+    max_width: Option<i32>,
+    wrap_indent: i32,
+    width_of: M,
+) -> Vec<(Vec<Span>, bool)> {
+    let text: String = spans.iter().map(|span| &span.0[..]).collect();
+    let mut starts = Vec::with_capacity(spans.len());
+    let mut offset = 0;
+    for span in spans {
+        starts.push(offset);
+        offset += span.0.len();
+    }
+
+    break_lines(&text, max_width, wrap_indent, width_of).into_iter().map(|(line, soft)| {
+        let line_spans = spans.iter().zip(&starts).filter_map(|(&(ref span, color, y_offset), &start)| {
+            let from = line.start.max(start);
+            let to = line.end.min(start + span.len());
+            if from < to { Some((text[from..to].to_string(), color, y_offset)) } else { None }
+        }).collect();
+        (line_spans, soft)
+    }).collect()
+}
+
+///
+/// A piece of text to draw: its text, its position in pixels relative to the
+/// top left corner of the screen and its color
+///
+pub type PlacedText = (String, [i32; 2], [f32; 4]);
+
+///
+/// Place lines of text on screen at `position` according to the alignment,
+/// wrap indent and tab stops in `options`. Returns the pieces
+/// of text to draw and the screen rectangle `[x, y, width, height]` of every
+/// line.
+///
+pub fn place_lines<M: Fn(&str) -> i32>(
+    lines: &[Line],
+    position: [i32; 2],
+    line_height: i32,
+    options: &TextOptions,
+    width_of: &M,
+) -> (Vec<PlacedText>, Vec<[i32; 4]>) {
+    let top = align_block(position[1], line_height * (lines.len() as i32), options.vertical_align);
+    let mut placed = Vec::new();
+    let mut rects = Vec::new();
+    for (i, &(ref line, width, soft)) in lines.iter().enumerate() {
+        let indent = if soft { options.continuation_indent() } else { 0 };
+        let left = align_line(position[0], width, options.align) + indent;
+        let y = top + line_height * (i as i32);
+
+        // Width to spread over the spaces of a justified line that wrapping broke
+        let gaps = line.iter().map(|span| span.0.matches(' ').count()).sum::<usize>() as i32;
+        let wrapped = lines.get(i + 1).is_some_and(|next| next.2);
+        let extra = match (options.align, options.max_width) {
+            (TextAlign::Justify, Some(max_width)) if wrapped && gaps > 0 => (max_width - indent - width).max(0),
+            _ => 0,
+        };
+        rects.push([left, y, width + extra, line_height]);
+
+        let mut pen = 0;
+        let mut spaces = 0;
+        for &(ref text, color, y_offset) in line {
+            let (pieces, next_pen) = expand_tabs(text, pen, options.tab_width, width_of);
+            for (piece, x) in pieces {
+                if extra == 0 {
+                    placed.push((piece.to_string(), [left + x, y + y_offset], color));
+                    continue;
+                }
+                // Place each word on its own, moved by its share of the extra width
+                let mut word_start = 0;
+                for word in piece.split(' ') {
+                    if !word.is_empty() {
+                        let word_x = x + advance(&piece[..word_start], width_of) + extra * spaces / gaps;
+                        placed.push((word.to_string(), [left + word_x, y + y_offset], color));
+                    }
+                    word_start += word.len() + 1;
+                    spaces += 1;
+                }
+                spaces -= 1;
+            }
+            pen = next_pen;
+        }
+    }
+    (placed, rects)
+}
+
+///
+/// Split `text` into spans as `options` asks: by its color tags with
+/// `markup`, or as one span in `color` otherwise
+///
+pub fn parse_text(text: &str, color: [f32; 4], options: &TextOptions) -> Vec<Span> {
+    if options.markup {
+        parse_markup(text, color)
+    } else {
+        vec![(text.to_string(), color, 0)]
+    }
+}
+
+///
+/// Break spans into lines according to `options`, returning each line's
+/// spans and width, and whether it was wrapped
+///
+pub fn layout_lines<M: Fn(&str) -> i32>(spans: &[Span], options: &TextOptions, width_of: &M) -> Vec<Line> {
+    let tab_width = options.tab_width;
+    let wrap_indent = options.continuation_indent();
+    wrap_spans(spans, options.max_width, wrap_indent, |text| line_width(text, tab_width, width_of))
+        .into_iter()
+        .map(|(line, soft)| {
+            let line_text: String = line.iter().map(|span| &span.0[..]).collect();
+            let width = line_width(&line_text, tab_width, width_of);
+            (line, width, soft)
+        })
+        .collect()
+}
+
+///
+/// Lay out spans at `position` the way `DebugRenderer` draws them, returning
+/// the pieces of text to draw and the screen rectangle of every line as
+/// `place_lines` does
+///
+pub fn layout_spans<M: Fn(&str) -> i32>(
+    spans: &[Span],
+    position: [i32; 2],
+    line_height: i32,
+    options: &TextOptions,
+    width_of: &M,
+) -> (Vec<PlacedText>, Vec<[i32; 4]>) {
+    let lines = layout_lines(spans, options, width_of);
+    place_lines(&lines, position, line_height, options, width_of)
+}
+
+///
+/// Lay out `text` in `color` as `DebugRenderer::draw_text_on_screen_with`
+/// would draw it at `[0, 0]`, without needing a text renderer. `width_of`
+/// gives the width in pixels of a string as measured by the font, e.g.
+/// `|text| text_renderer.measure(text).0`, and `line_height` the height of
+/// a line. Characters the font has no glyph for are not replaced.
+///
+pub fn layout_text<M: Fn(&str) -> i32>(
+    text: &str,
+    color: [f32; 4],
+    options: &TextOptions,
+    line_height: i32,
+    width_of: M,
+) -> Vec<PlacedText> {
+    let spans = parse_text(text, color, options);
+    layout_spans(&spans, [0, 0], line_height, options, &width_of).0
+}
+
+///
+/// Replace the characters of a span for which `missing` holds with
+/// `fallback`. With a `tint`, the replacements are split off into spans of
+/// their own drawn in that color.
+///
+pub fn replace_missing<F: Fn(char) -> bool>(span: &Span, fallback: char, tint: Option<[f32; 4]>, missing: F) -> Vec<Span> {
+    let (ref text, color, y_offset) = *span;
+    let tint = match tint {
+        Some(tint) => tint,
+        None => {
+            let text = text.chars().map(|ch| if missing(ch) { fallback } else { ch }).collect();
+            return vec![(text, color, y_offset)];
+        }
+    };
+
+    let mut spans: Vec<Span> = Vec::new();
+    let mut run_missing = None;
+    for ch in text.chars() {
+        let ch_missing = missing(ch);
+        if run_missing != Some(ch_missing) {
+            spans.push((String::new(), if ch_missing { tint } else { color }, y_offset));
+            run_missing = Some(ch_missing);
+        }
+        if let Some(last) = spans.last_mut() {
+            last.0.push(if ch_missing { fallback } else { ch });
+        }
+    }
+    if spans.is_empty() {
+        spans.push(span.clone());
+    }
+    spans
+}
+
+///
+/// The number of glyphs gfx_text draws for `text`, which leaves out line
+/// breaks, tabs and characters for which `missing` holds
+///
+pub fn glyph_count<F: Fn(char) -> bool>(text: &str, missing: F) -> usize {
+    text.chars().filter(|&ch| ch != '\n' && ch != '\t' && !missing(ch)).count()
+}
+
+///
+/// Split text with inline color tags into spans. `{#rrggbb}`, `{#rrggbbaa}`
+/// or a color name from `NAMED_COLORS` in square brackets, like `[red]`,
+/// switches to a new color, and `{/}` or `[/]` back to `base_color`. Anything
+/// else in braces or brackets is kept as literal text.
+///
+pub fn parse_markup(text: &str, base_color: [f32; 4]) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut current = String::new();
+    let mut color = base_color;
+    let mut rest = text;
+
+    while let Some(ch) = rest.chars().next() {
+        let close = match ch {
+            '{' => rest.find('}'),
+            '[' => rest.find(']'),
+            _ => None,
+        };
+        if let Some(close) = close {
+            let tag = &rest[1..close];
+            let tag_color = if ch == '{' { parse_color_tag(tag, base_color) } else { parse_named_tag(tag, base_color) };
+            if let Some(tag_color) = tag_color {
+                if !current.is_empty() {
+                    spans.push((mem::take(&mut current), color, 0));
+                }
+                color = tag_color;
+                rest = &rest[close + 1..];
+                continue;
+            }
+        }
+        current.push(ch);
+        rest = &rest[ch.len_utf8()..];
+    }
+
+    if !current.is_empty() || spans.is_empty() {
+        spans.push((current, color, 0));
+    }
+    spans
+}
+
+///
+/// Colors that can be switched to by name with `[name]` in markup
+///
+const NAMED_COLORS: [(&str, [f32; 4]); 11] = [
+    ("white", [1.0, 1.0, 1.0, 1.0]),
+    ("black", [0.0, 0.0, 0.0, 1.0]),
+    ("gray", [0.5, 0.5, 0.5, 1.0]),
+    ("red", [1.0, 0.0, 0.0, 1.0]),
+    ("green", [0.0, 1.0, 0.0, 1.0]),
+    ("blue", [0.0, 0.0, 1.0, 1.0]),
+    ("yellow", [1.0, 1.0, 0.0, 1.0]),
+    ("cyan", [0.0, 1.0, 1.0, 1.0]),
+    ("magenta", [1.0, 0.0, 1.0, 1.0]),
+    ("orange", [1.0, 0.5, 0.0, 1.0]),
+    ("purple", [0.5, 0.0, 1.0, 1.0]),
+];
+
+fn parse_named_tag(tag: &str, base_color: [f32; 4]) -> Option<[f32; 4]> {
+    if tag == "/" {
+        return Some(base_color);
+    }
+    NAMED_COLORS.iter().find(|&&(name, _)| name == tag).map(|&(_, color)| color)
+}
+
+fn parse_color_tag(tag: &str, base_color: [f32; 4]) -> Option<[f32; 4]> {
+    if tag == "/" {
+        return Some(base_color);
+    }
+    let hex = tag.strip_prefix('#')?;
+    if (hex.len() != 6 && hex.len() != 8) || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).ok().map(|c| c as f32 / 255.0);
+    Some([channel(0)?, channel(1)?, channel(2)?, if hex.len() == 8 { channel(3)? } else { 1.0 }])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+    const WHITE: [f32; 4] = [1.0; 4];
+
+    // Fixed 10 pixel advance, with the last glyph's ink 2 pixels narrower, like
+    // gfx_text's measure
+    fn width_of(text: &str) -> i32 {
+        match text.chars().count() as i32 {
+            0 => 0,
+            n => 10 * n - 2,
+        }
+    }
+
+    fn lines(text: &str, max_width: Option<i32>) -> Vec<&str> {
+        break_lines(text, max_width, 0, width_of).into_iter().map(|(line, _)| &text[line]).collect()
+    }
+
+    #[test]
+    fn advance_excludes_the_ink_correction() {
+        assert_eq!(advance("abc", width_of), 30);
+    }
+
+    #[test]
+    fn break_lines_splits_at_newlines() {
+        assert_eq!(lines("ab\n\ncd", None), vec!["ab", "", "cd"]);
+    }
+
+    #[test]
+    fn break_lines_wraps_between_words() {
+        assert_eq!(lines("aa bb cc dd", Some(50)), vec!["aa bb", "cc dd"]);
+    }
+
+    #[test]
+    fn break_lines_keeps_a_long_word_on_its_own_line() {
+        assert_eq!(lines("a bbbbbbbb c", Some(40)), vec!["a", "bbbbbbbb", "c"]);
+    }
+
+    #[test]
+    fn break_lines_trims_spaces_at_soft_breaks() {
+        assert_eq!(lines("aaa  bbb   ccc", Some(40)), vec!["aaa", "bbb", "ccc"]);
+        assert_eq!(lines("  aa ", Some(100)), vec!["  aa "]);
+    }
+
+    #[test]
+    fn break_lines_marks_soft_breaks_and_narrows_indented_lines() {
+        let text = "aa bb cc\ndd";
+        let marked: Vec<_> = break_lines(text, Some(50), 20, width_of).into_iter()
+            .map(|(line, soft)| (&text[line], soft))
+            .collect();
+        assert_eq!(marked, vec![("aa bb", false), ("cc", true), ("dd", false)]);
+        assert_eq!(lines("aa bb cc dd", Some(50)), vec!["aa bb", "cc dd"]);
+        let indented: Vec<_> = break_lines("aa bb cc dd", Some(50), 20, width_of).into_iter()
+            .map(|(line, _)| line)
+            .collect();
+        assert_eq!(indented, vec![0..5, 6..8, 9..11]);
+    }
+
+    #[test]
+    fn wrap_spans_splits_spans_at_line_breaks() {
+        let spans = vec![("aa b".to_string(), RED, 0), ("b cc".to_string(), WHITE, 3)];
+        assert_eq!(wrap_spans(&spans, Some(40), 0, width_of), vec![
+            (vec![("aa".to_string(), RED, 0)], false),
+            (vec![("b".to_string(), RED, 0), ("b".to_string(), WHITE, 3)], true),
+            (vec![("cc".to_string(), WHITE, 3)], true),
+        ]);
+    }
+
+    fn place(lines: &[Line], position: [i32; 2], options: &TextOptions) -> Vec<PlacedText> {
+        place_lines(lines, position, 20, options, &width_of).0
+    }
+
+    #[test]
+    fn place_lines_moves_spans_by_their_offset() {
+        let lines = vec![(vec![("a".to_string(), WHITE, 0), ("b".to_string(), RED, 5)], 18, false)];
+        assert_eq!(place(&lines, [100, 50], &TextOptions::default()), vec![
+            ("a".to_string(), [100, 50], WHITE),
+            ("b".to_string(), [110, 55], RED),
+        ]);
+    }
+
+    #[test]
+    fn place_lines_aligns_lines_and_blocks() {
+        let lines = vec![(vec![("ab".to_string(), WHITE, 0)], 18, false), (vec![("c".to_string(), WHITE, 0)], 8, false)];
+        let options = TextOptions {
+            align: TextAlign::Right,
+            vertical_align: TextVerticalAlign::Bottom,
+            ..TextOptions::default()
+        };
+        let (placed, rects) = place_lines(&lines, [100, 50], 20, &options, &width_of);
+        assert_eq!(placed, vec![("ab".to_string(), [82, 10], WHITE), ("c".to_string(), [92, 30], WHITE)]);
+        assert_eq!(rects, vec![[82, 10, 18, 20], [92, 30, 8, 20]]);
+    }
+
+    #[test]
+    fn place_lines_indents_wrapped_lines() {
+        let lines = vec![
+            (vec![("aa".to_string(), WHITE, 0)], 18, false),
+            (vec![("bb".to_string(), WHITE, 0)], 18, true),
+            (vec![("cc".to_string(), WHITE, 0)], 18, false),
+        ];
+        let options = TextOptions { wrap_indent: 15, ..TextOptions::default() };
+        let starts: Vec<_> = place(&lines, [100, 0], &options).into_iter().map(|(_, position, _)| position[0]).collect();
+        assert_eq!(starts, vec![100, 115, 100]);
+    }
+
+    #[test]
+    fn place_lines_justifies_wrapped_lines() {
+        let spans = vec![("aa bb c dd e".to_string(), WHITE, 0)];
+        let options = TextOptions { max_width: Some(70), align: TextAlign::Justify, ..TextOptions::default() };
+        let lines: Vec<Line> = wrap_spans(&spans, options.max_width, 0, width_of).into_iter().map(|(line, soft)| {
+            let width = width_of(&line[0].0);
+            (line, width, soft)
+        }).collect();
+        assert_eq!(lines.iter().map(|line| &line.0[0].0[..]).collect::<Vec<_>>(), vec!["aa bb c", "dd e"]);
+
+        let placed = place(&lines, [100, 0], &options);
+        // The wrapped line's spaces share the 2 spare pixels and its last
+        // glyph ends at the right margin, while the last line is left aligned
+        assert_eq!(placed, vec![
+            ("aa".to_string(), [100, 0], WHITE),
+            ("bb".to_string(), [131, 0], WHITE),
+            ("c".to_string(), [162, 0], WHITE),
+            ("dd e".to_string(), [100, 20], WHITE),
+        ]);
+        assert_eq!(162 + width_of("c"), 100 + 70);
+    }
+
+    #[test]
+    fn place_lines_leaves_single_words_left_aligned_when_justifying() {
+        let lines = vec![
+            (vec![("aaaa".to_string(), WHITE, 0)], 38, false),
+            (vec![("bb".to_string(), WHITE, 0)], 18, true),
+        ];
+        let options = TextOptions { max_width: Some(70), align: TextAlign::Justify, ..TextOptions::default() };
+        assert_eq!(place(&lines, [100, 0], &options)[0], ("aaaa".to_string(), [100, 0], WHITE));
+    }
+
+    #[test]
+    fn layout_text_places_runs_of_glyphs() {
+        let options = TextOptions {
+            max_width: Some(50),
+            align: TextAlign::Center,
+            markup: true,
+            ..TextOptions::default()
+        };
+        assert_eq!(layout_text("ab [red]cd[/]\nef", WHITE, &options, 20, width_of), vec![
+            ("ab ".to_string(), [-24, 0], WHITE),
+            ("cd".to_string(), [6, 0], RED),
+            ("ef".to_string(), [-9, 20], WHITE),
+        ]);
+    }
+
+    #[test]
+    fn replace_missing_tints_fallback_characters() {
+        let missing = |ch| ch == '\u{2603}';
+        let span = ("a\u{2603}\u{2603}b".to_string(), WHITE, 2);
+        assert_eq!(replace_missing(&span, '?', None, missing), vec![("a??b".to_string(), WHITE, 2)]);
+        assert_eq!(replace_missing(&span, '?', Some(RED), missing), vec![
+            ("a".to_string(), WHITE, 2),
+            ("??".to_string(), RED, 2),
+            ("b".to_string(), WHITE, 2),
+        ]);
+    }
+
+    #[test]
+    fn glyph_count_skips_line_breaks_tabs_and_missing_glyphs() {
+        assert_eq!(glyph_count("ab c\td\ne", |_| false), 6);
+        assert_eq!(glyph_count("a\u{2603}b", |ch| ch == '\u{2603}'), 2);
+    }
+
+    #[test]
+    fn parse_markup_switches_colors() {
+        assert_eq!(parse_markup("a{#ff0000}b{/}c", WHITE), vec![
+            ("a".to_string(), WHITE, 0),
+            ("b".to_string(), RED, 0),
+            ("c".to_string(), WHITE, 0),
+        ]);
+        assert_eq!(parse_markup("{#00ff0080}x", WHITE), vec![("x".to_string(), [0.0, 1.0, 0.0, 128.0 / 255.0], 0)]);
+    }
+
+    #[test]
+    fn parse_markup_keeps_unknown_tags() {
+        assert_eq!(parse_markup("{x}{#12}", WHITE), vec![("{x}{#12}".to_string(), WHITE, 0)]);
+        assert_eq!(parse_markup("[bogus]x[0]", WHITE), vec![("[bogus]x[0]".to_string(), WHITE, 0)]);
+    }
+
+    #[test]
+    fn parse_markup_switches_to_named_colors() {
+        let spans = parse_markup("[red]error[/] ok", WHITE);
+        assert_eq!(spans, vec![("error".to_string(), RED, 0), (" ok".to_string(), WHITE, 0)]);
+        let text: String = spans.iter().map(|span| &span.0[..]).collect();
+        assert_eq!(text, "error ok");
+    }
+
+    #[test]
+    fn expand_tabs_moves_to_tab_stops() {
+        assert_eq!(expand_tabs("a\tbc\td", 0, Some(40), &width_of), (vec![("a", 0), ("bc", 40), ("d", 80)], 90));
+        assert_eq!(expand_tabs("a\tb", 0, None, &width_of), (vec![("a\tb", 0)], 30));
+    }
+
+    #[test]
+    fn line_width_measures_to_the_last_ink() {
+        assert_eq!(line_width("ab", None, &width_of), 18);
+        assert_eq!(line_width("a\tb", Some(40), &width_of), 48);
+    }
+
+    fn caret(text: &str, char_index: usize, options: &TextOptions) -> [i32; 2] {
+        caret_offset(text, char_index, 20, options, ScreenOrigin::TopLeft, &width_of)
+    }
+
+    #[test]
+    fn caret_offset_sums_advances() {
+        let options = TextOptions::default();
+        let tabs = TextOptions { tab_width: Some(40), ..TextOptions::default() };
+        assert_eq!(caret("abc", 0, &options), [0, 0]);
+        assert_eq!(caret("abc", 2, &options), [advance("a", width_of) + advance("b", width_of), 0]);
+        assert_eq!(caret("abc", 9, &options), [30, 0]);
+        assert_eq!(caret("a\tb\ncd", 3, &tabs), [50, 0]);
+        assert_eq!(caret("a\tb\ncd", 5, &tabs), [10, 20]);
+    }
+
+    #[test]
+    fn caret_offset_follows_wrapping_and_alignment() {
+        let wrapped = TextOptions { max_width: Some(50), wrap_indent: 5, ..TextOptions::default() };
+        assert_eq!(caret("aa bb cc", 2, &wrapped), [20, 0]);
+        assert_eq!(caret("aa bb cc", 7, &wrapped), [15, 20]);
+        let centered = TextOptions { align: TextAlign::Center, ..TextOptions::default() };
+        assert_eq!(caret("abc", 1, &centered), [-4, 0]);
+    }
+
+    #[test]
+    fn caret_offset_skips_markup_once_parsed() {
+        let options = TextOptions { markup: true, ..TextOptions::default() };
+        let spans = parse_text("[red]ab[/]c", WHITE, &options);
+        let text: String = spans.iter().map(|span| &span.0[..]).collect();
+        assert_eq!(caret(&text, 2, &options), [20, 0]);
+    }
+
+    #[test]
+    fn caret_offset_measures_to_the_line_bottom_from_the_bottom_left() {
+        let options = TextOptions::default();
+        let caret = |char_index| caret_offset("ab\ncd", char_index, 20, &options, ScreenOrigin::BottomLeft, &width_of);
+        // The text extends upwards from its position, so its first line is
+        // the higher one
+        assert_eq!(caret(1), [10, 20]);
+        assert_eq!(caret(4), [10, 0]);
+    }
+
+    #[test]
+    fn clip_piece_keeps_characters_inside() {
+        assert_eq!(clip_piece("abcdef", 0, 10, 38, &width_of), Some(("bcd".to_string(), 10)));
+        assert_eq!(clip_piece("abcdef", 0, 10, 37, &width_of), Some(("bc".to_string(), 10)));
+        assert_eq!(clip_piece("abc", 0, 100, 200, &width_of), None);
+    }
+
+    #[test]
+    fn align_line_and_block_anchor_the_text() {
+        assert_eq!(align_line(100, 40, TextAlign::Center), 80);
+        assert_eq!(align_line(100, 40, TextAlign::Right), 60);
+        assert_eq!(align_block(100, 40, TextVerticalAlign::Bottom), 60);
+    }
+
+    #[test]
+    fn screen_origin_flips_y() {
+        let origin = ScreenOrigin::BottomLeft;
+        assert_eq!(ScreenOrigin::TopLeft.y_from_top(0, 600), 0);
+        assert_eq!(origin.y_from_top(0, 600), 600);
+        assert_eq!(origin.rect_from_top([10, 0, 100, 20], 600), [10, 580, 100, 20]);
+        // A label at y = 0 sits at the top from the top left and just above
+        // the bottom edge from the bottom left
+        assert_eq!(ScreenOrigin::TopLeft.vertical_align_from_top(TextVerticalAlign::Top), TextVerticalAlign::Top);
+        assert_eq!(origin.vertical_align_from_top(TextVerticalAlign::Top), TextVerticalAlign::Bottom);
+        assert_eq!(align_block(origin.y_from_top(0, 600), 20, TextVerticalAlign::Bottom), 580);
+    }
+
+    #[test]
+    fn screen_anchor_resolves_against_the_screen_size() {
+        assert_eq!(
+            ScreenAnchor::TopRight.resolve([10, 5], [800, 600]),
+            ([790, 5], TextAlign::Right, TextVerticalAlign::Top)
+        );
+        assert_eq!(
+            ScreenAnchor::Center.resolve([0, 0], [800, 600]),
+            ([400, 300], TextAlign::Center, TextVerticalAlign::Center)
+        );
+        assert_eq!(
+            ScreenAnchor::BottomLeft.resolve([10, 5], [800, 600]),
+            ([10, 595], TextAlign::Left, TextVerticalAlign::Bottom)
+        );
+    }
+}
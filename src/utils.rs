@@ -6,6 +6,17 @@ use gfx::{
     Resources,
 };
 use gfx::memory::Bind;
+use std::collections::HashMap;
+use vecmath::{
+    col_mat4_transform,
+    vec3_add,
+    vec3_cross,
+    vec3_len,
+    vec3_normalized,
+    vec3_scale,
+    vec3_square_len,
+    vec3_sub,
+};
 
 pub fn grow_buffer<R: Resources, F: Factory<R>, T>(
     factory: &mut F,
@@ -20,3 +31,231 @@ pub fn grow_buffer<R: Resources, F: Factory<R>, T>(
     factory.create_buffer(size, buffer_role, memory::Usage::Dynamic, Bind::empty())
         .expect("Could not create buffer")
 }
+
+///
+/// Raise the alpha of a color to at least `min_alpha`
+///
+pub fn clamp_alpha(color: [f32; 4], min_alpha: f32) -> [f32; 4] {
+    [color[0], color[1], color[2], color[3].max(min_alpha)]
+}
+
+///
+/// Multiply the alpha of a color by `factor`
+///
+pub fn scale_alpha(color: [f32; 4], factor: f32) -> [f32; 4] {
+    [color[0], color[1], color[2], color[3] * factor]
+}
+
+///
+/// Transform a point by a column-major 4x4 matrix, including the divide by w
+///
+pub fn transform_point(matrix: [[f32; 4]; 4], point: [f32; 3]) -> [f32; 3] {
+    let p = col_mat4_transform(matrix, [point[0], point[1], point[2], 1.0]);
+    [p[0] / p[3], p[1] / p[3], p[2] / p[3]]
+}
+
+///
+/// Find the pixel position, relative to the top left corner of a screen
+/// `screen_size` pixels large, that gfx_text would place world text at. Like
+/// gfx_text this divides by clip-space z, so points behind the viewer, or
+/// with a z below `near_clip`, give `None`.
+///
+pub fn project_to_screen(projection: [[f32; 4]; 4], point: [f32; 3], screen_size: [i32; 2], near_clip: f32) -> Option<[f32; 2]> {
+    let clip = col_mat4_transform(projection, [point[0], point[1], point[2], 1.0]);
+    if clip[2] <= 0.0 || clip[2] < near_clip {
+        return None;
+    }
+    let x = (clip[0] / clip[2] + 1.0) * 0.5 * screen_size[0] as f32;
+    let y = (1.0 - clip[1] / clip[2]) * 0.5 * screen_size[1] as f32;
+    Some([x, y])
+}
+
+///
+/// Turn a pixel position into a whole one, rounding to the nearest pixel with
+/// `snap` and dropping the fraction otherwise
+///
+pub fn to_pixel(position: [f32; 2], snap: bool) -> [i32; 2] {
+    if snap {
+        [position[0].round() as i32, position[1].round() as i32]
+    } else {
+        [position[0].floor() as i32, position[1].floor() as i32]
+    }
+}
+
+///
+/// Find two unit vectors that, together with `normal`, form an orthonormal basis
+///
+pub fn plane_basis(normal: [f32; 3]) -> ([f32; 3], [f32; 3]) {
+    let normal = vec3_normalized(normal);
+    let helper = if normal[0].abs() < 0.9 { [1.0, 0.0, 0.0] } else { [0.0, 1.0, 0.0] };
+    let u = vec3_normalized(vec3_cross(normal, helper));
+    let v = vec3_cross(normal, u);
+    (u, v)
+}
+
+///
+/// Unproject the corners of the clip-space box between the NDC depths `near`
+/// and `far`. Corners are indexed with bit 0 selecting +x, bit 1 +y and bit 2
+/// the far plane.
+///
+pub fn frustum_corners(inverse_view_proj: [[f32; 4]; 4], near: f32, far: f32) -> [[f32; 3]; 8] {
+    let mut corners = [[0.0; 3]; 8];
+    for (i, corner) in corners.iter_mut().enumerate() {
+        let ndc = [
+            if i & 1 == 0 { -1.0 } else { 1.0 },
+            if i & 2 == 0 { -1.0 } else { 1.0 },
+            if i & 4 == 0 { near } else { far },
+        ];
+        *corner = transform_point(inverse_view_proj, ndc);
+    }
+    corners
+}
+
+///
+/// Approximate the bounding sphere of a point set with Ritter's algorithm,
+/// returning its center and radius, or `None` for an empty set
+///
+pub fn bounding_sphere(points: &[[f32; 3]]) -> Option<([f32; 3], f32)> {
+    let first = *points.first()?;
+    let farthest_from = |from: [f32; 3]| {
+        points.iter().cloned().fold(from, |best, p| {
+            if vec3_square_len(vec3_sub(p, from)) > vec3_square_len(vec3_sub(best, from)) { p } else { best }
+        })
+    };
+
+    // Start from the span between two far apart points...
+    let a = farthest_from(first);
+    let b = farthest_from(a);
+    let mut center = vec3_scale(vec3_add(a, b), 0.5);
+    let mut radius = vec3_len(vec3_sub(b, a)) * 0.5;
+
+    // ...then grow the sphere just enough to take in any point still outside
+    for &p in points {
+        let distance = vec3_len(vec3_sub(p, center));
+        if distance > radius {
+            let new_radius = (radius + distance) * 0.5;
+            center = vec3_add(center, vec3_scale(vec3_sub(p, center), (new_radius - radius) / distance));
+            radius = new_radius;
+        }
+    }
+
+    Some((center, radius))
+}
+
+///
+/// Build a unit sphere by subdividing an icosahedron, returning its vertices
+/// and triangles (counter-clockwise seen from outside)
+///
+pub fn icosphere(subdivisions: usize) -> (Vec<[f32; 3]>, Vec<[usize; 3]>) {
+    let t = (1.0 + 5.0f32.sqrt()) / 2.0;
+    let mut vertices: Vec<[f32; 3]> = [
+        [-1.0, t, 0.0], [1.0, t, 0.0], [-1.0, -t, 0.0], [1.0, -t, 0.0],
+        [0.0, -1.0, t], [0.0, 1.0, t], [0.0, -1.0, -t], [0.0, 1.0, -t],
+        [t, 0.0, -1.0], [t, 0.0, 1.0], [-t, 0.0, -1.0], [-t, 0.0, 1.0],
+    ].iter().map(|&v| vec3_normalized(v)).collect();
+    let mut triangles = vec![
+        [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+        [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+        [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+        [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        let mut midpoints = HashMap::new();
+        let mut midpoint = |a: usize, b: usize, vertices: &mut Vec<[f32; 3]>| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                vertices.push(vec3_normalized(vec3_add(vertices[a], vertices[b])));
+                vertices.len() - 1
+            })
+        };
+        triangles = triangles.iter().flat_map(|&[a, b, c]| {
+            let ab = midpoint(a, b, &mut vertices);
+            let bc = midpoint(b, c, &mut vertices);
+            let ca = midpoint(c, a, &mut vertices);
+            vec![[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+        }).collect();
+    }
+
+    (vertices, triangles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vecmath::{mat4_id, vec3_dot};
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn bounding_sphere_contains_all_points() {
+        let points = [[1.0, 0.0, 0.0], [-1.0, 0.0, 0.0], [0.0, 3.0, 0.0], [0.0, 0.0, -2.0], [0.5, 0.5, 0.5]];
+        let (center, radius) = bounding_sphere(&points).unwrap();
+        for &p in &points {
+            assert!(vec3_len(vec3_sub(p, center)) <= radius + 1e-4);
+        }
+        assert_eq!(bounding_sphere(&[]), None);
+    }
+
+    #[test]
+    fn plane_basis_is_orthonormal() {
+        for &normal in &[[0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [1.0, 2.0, -3.0]] {
+            let (u, v) = plane_basis(normal);
+            let normal = vec3_normalized(normal);
+            assert_close(vec3_len(u), 1.0);
+            assert_close(vec3_len(v), 1.0);
+            assert_close(vec3_dot(u, v), 0.0);
+            assert_close(vec3_dot(u, normal), 0.0);
+            assert_close(vec3_dot(v, normal), 0.0);
+        }
+    }
+
+    #[test]
+    fn icosphere_vertices_lie_on_the_unit_sphere() {
+        let (vertices, triangles) = icosphere(0);
+        assert_eq!((vertices.len(), triangles.len()), (12, 20));
+        let (vertices, triangles) = icosphere(1);
+        assert_eq!((vertices.len(), triangles.len()), (42, 80));
+        for &v in &vertices {
+            assert_close(vec3_len(v), 1.0);
+        }
+    }
+
+    #[test]
+    fn clamp_alpha_raises_alpha_below_the_minimum() {
+        assert_eq!(clamp_alpha([0.2, 0.4, 0.6, 0.1], 0.5), [0.2, 0.4, 0.6, 0.5]);
+        assert_eq!(clamp_alpha([0.2, 0.4, 0.6, 0.8], 0.5), [0.2, 0.4, 0.6, 0.8]);
+        // The default minimum of 0.0 leaves colors unchanged
+        assert_eq!(clamp_alpha([0.2, 0.4, 0.6, 0.0], 0.0), [0.2, 0.4, 0.6, 0.0]);
+    }
+
+    #[test]
+    fn scale_alpha_only_changes_alpha() {
+        assert_eq!(scale_alpha([0.2, 0.4, 0.6, 0.8], 0.5), [0.2, 0.4, 0.6, 0.4]);
+        assert_eq!(scale_alpha([0.2, 0.4, 0.6, 0.8], 1.0), [0.2, 0.4, 0.6, 0.8]);
+    }
+
+    #[test]
+    fn project_to_screen_maps_clip_space_to_pixels() {
+        let projection: [[f32; 4]; 4] = mat4_id();
+        assert_eq!(project_to_screen(projection, [0.0, 0.0, 1.0], [800, 600], 0.0), Some([400.0, 300.0]));
+        assert_eq!(project_to_screen(projection, [-1.0, 1.0, 1.0], [800, 600], 0.0), Some([0.0, 0.0]));
+        assert_eq!(project_to_screen(projection, [0.0, 0.0, -1.0], [800, 600], 0.0), None);
+    }
+
+    #[test]
+    fn project_to_screen_drops_points_closer_than_the_near_clip() {
+        let projection: [[f32; 4]; 4] = mat4_id();
+        assert_eq!(project_to_screen(projection, [0.0, 0.0, 0.5], [800, 600], 1.0), None);
+        assert_eq!(project_to_screen(projection, [0.0, 0.0, 2.0], [800, 600], 1.0), Some([400.0, 300.0]));
+    }
+
+    #[test]
+    fn to_pixel_rounds_when_snapping() {
+        let projection: [[f32; 4]; 4] = mat4_id();
+        let position = project_to_screen(projection, [0.001, -0.001, 1.0], [801, 601], 0.0).unwrap();
+        assert_eq!(to_pixel(position, true), [401, 301]);
+        assert_eq!(to_pixel(position, false), [400, 300]);
+    }
+}